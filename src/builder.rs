@@ -0,0 +1,1683 @@
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, Context};
+use rand::{rngs::StdRng, SeedableRng};
+use rayon::ThreadPoolBuilder;
+use tokio::io::DuplexStream;
+use wasmtime::{
+    component::{types, Component, Linker, Val},
+    Config, Engine, InstanceAllocationStrategy, PoolingAllocationConfig, Store,
+};
+use wasmtime_wasi::{pipe::AsyncReadStream, AsyncStdinStream, DirPerms, FilePerms, WasiCtxBuilder};
+
+use crate::{
+    clock::FixedClock,
+    command, config_store,
+    epoch::{self, Deadline, EpochTicker},
+    events::EventPublisher,
+    global,
+    instance::ExportFallback,
+    load::ValidationPolicy,
+    progress::{self, ProgressCallback},
+    quarantine::{QuarantinePolicy, QuarantineTracker},
+    rate_limit::{RateLimit, RateLimits},
+    shim,
+    stdio::{CapturedPipe, STDIN_PIPE_BUFFER},
+    store::{StoreSettings, WasiSetting},
+    trace::traced,
+    FootprintEstimate, ImportShim, NestedView, Runtime, RuntimeError, RuntimeView,
+};
+
+/// Wasmtime's default `max_wasm_stack`.
+const DEFAULT_MAX_WASM_STACK: usize = 512 * 1024;
+
+/// How often the epoch advances to check deadlines when no poll budget is set.
+const DEADLINE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Stack kept free for host frames on top of `max_wasm_stack` in async fiber stacks.
+const HOST_STACK_HEADROOM: usize = 1024 * 1024;
+
+/// Mixed into the seed of [`RuntimeBuilder::with_seeded_rng`] for the insecure random sources, so
+/// their output doesn't repeat the secure source's.
+const INSECURE_SEED_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// How long building the runtime's linker took, measured with a monotonic clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkerTimings {
+    /// Time spent adding WASI and `wasi:http` to the linker, zero when both are disabled.
+    pub wasi: Duration,
+    /// Time spent in [`NestedView::add_all_to_linker`].
+    pub nested_view: Duration,
+    /// Time for the whole linker setup, including global host functions and import shims.
+    pub total: Duration,
+}
+
+type LinkerHook<T> = Box<dyn FnOnce(&mut Linker<RuntimeView<T>>) -> anyhow::Result<()> + Send>;
+
+/// Configures and builds a [`Runtime`].
+///
+/// Only the nested view is required. Every other option defaults to what [`crate::runtime`]
+/// uses, so `RuntimeBuilder::new().with_wasi(w).nested_view(v).build()` is equivalent to
+/// `runtime(w, v)`.
+pub struct RuntimeBuilder<T: NestedView> {
+    with_wasi: bool,
+    with_wasi_http: bool,
+    nested_view: Option<T>,
+    poll_budget: Option<Duration>,
+    validation_policy: Option<ValidationPolicy>,
+    warm_concurrency: Option<NonZeroUsize>,
+    max_wasm_stack: Option<usize>,
+    resource_table_capacity: Option<usize>,
+    export_fallback: Option<ExportFallback>,
+    shims: Vec<ImportShim>,
+    linker_hooks: Vec<LinkerHook<T>>,
+    max_lifetime: Option<Duration>,
+    max_instantiation_time: Option<Duration>,
+    call_timeout: Option<Duration>,
+    cancellable: bool,
+    rate_limits: RateLimits,
+    quarantine_policy: Option<QuarantinePolicy>,
+    on_progress: Option<ProgressCallback>,
+    config_store: Option<HashMap<String, String>>,
+    config: Option<Config>,
+    engine: Option<Engine>,
+    wasi: Vec<WasiSetting>,
+    stdin: Option<AsyncStdinStream>,
+    env_keys: Vec<String>,
+    inherit_env: bool,
+    capture_stdout: bool,
+    capture_stderr: bool,
+    preopens: Vec<(PathBuf, String, DirPerms, FilePerms)>,
+    fuel: Option<u64>,
+    max_memory_bytes: Option<usize>,
+    max_table_elements: Option<usize>,
+    cache: Option<Option<PathBuf>>,
+    pooling: Option<PoolingAllocationConfig>,
+    parallel_compilation: Option<bool>,
+    compilation_threads: Option<usize>,
+    backtraces: Option<bool>,
+    debug_info: Option<bool>,
+}
+
+impl<T> RuntimeBuilder<T>
+where
+    T: NestedView,
+{
+    pub fn new() -> Self {
+        Self {
+            with_wasi: false,
+            with_wasi_http: false,
+            nested_view: None,
+            poll_budget: None,
+            validation_policy: None,
+            warm_concurrency: None,
+            max_wasm_stack: None,
+            resource_table_capacity: None,
+            export_fallback: None,
+            shims: Vec::new(),
+            linker_hooks: Vec::new(),
+            max_lifetime: None,
+            max_instantiation_time: None,
+            call_timeout: None,
+            cancellable: false,
+            rate_limits: RateLimits::default(),
+            quarantine_policy: None,
+            on_progress: None,
+            config_store: None,
+            config: None,
+            engine: None,
+            wasi: Vec::new(),
+            stdin: None,
+            env_keys: Vec::new(),
+            inherit_env: false,
+            capture_stdout: false,
+            capture_stderr: false,
+            preopens: Vec::new(),
+            fuel: None,
+            max_memory_bytes: None,
+            max_table_elements: None,
+            cache: None,
+            pooling: None,
+            parallel_compilation: None,
+            compilation_threads: None,
+            backtraces: None,
+            debug_info: None,
+        }
+    }
+
+    pub fn with_wasi(mut self, with_wasi: bool) -> Self {
+        self.with_wasi = with_wasi;
+        self
+    }
+
+    /// Adds `wasi:http`, so guests can make outbound requests through
+    /// `wasi:http/outgoing-handler`.
+    ///
+    /// Without [`RuntimeBuilder::with_wasi`] this also adds the parts of WASI the `wasi:http/proxy`
+    /// world needs, like clocks, random and stdio.
+    pub fn with_wasi_http(mut self) -> Self {
+        self.with_wasi_http = true;
+        self
+    }
+
+    pub fn nested_view(mut self, nested_view: T) -> Self {
+        self.nested_view = Some(nested_view);
+        self
+    }
+
+    /// Starts the engine configuration from `config` instead of wasmtime's defaults, e.g. to
+    /// tune `cranelift_opt_level` or `wasm_simd`.
+    ///
+    /// The component model is always enabled on top of it, and options set on this builder (like
+    /// [`RuntimeBuilder::max_wasm_stack`]) override the corresponding `config` settings. Async
+    /// support is left as `config` has it, but WASI and async host functions need it, so building
+    /// fails with [`RuntimeError::SyncAsyncMismatch`] unless `config` enables it, e.g. by
+    /// starting from [`crate::default_config`].
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Reuses `engine` instead of creating one, so components compiled for one runtime can be
+    /// instantiated in others.
+    ///
+    /// The engine needs the component model and async support, e.g. by creating it from
+    /// [`crate::default_config`]. Building fails with [`RuntimeError::EngineCreation`] for an
+    /// engine without the component model. Wasmtime doesn't expose whether an engine has async
+    /// support, so an engine without it panics once WASI or an async host function is registered
+    /// with it. If the engine interrupts on epochs or consumes fuel, the runtime's stores get a
+    /// deadline that never expires and all the fuel they can hold, since the runtime doesn't
+    /// know what budget the engine was meant for.
+    ///
+    /// Options that configure the engine ([`with_config`], [`poll_budget`], [`max_wasm_stack`],
+    /// [`max_instantiation_time`], [`with_timeout`], [`cancellable`], [`with_fuel`],
+    /// [`enable_cache`], [`with_pooling_allocator`], [`parallel_compilation`],
+    /// [`with_backtraces`] and [`with_debug_info`]) can't be combined with a shared engine and
+    /// make [`RuntimeBuilder::build`] fail.
+    ///
+    /// [`with_config`]: RuntimeBuilder::with_config
+    /// [`poll_budget`]: RuntimeBuilder::poll_budget
+    /// [`max_wasm_stack`]: RuntimeBuilder::max_wasm_stack
+    /// [`max_instantiation_time`]: RuntimeBuilder::max_instantiation_time
+    /// [`with_timeout`]: RuntimeBuilder::with_timeout
+    /// [`cancellable`]: RuntimeBuilder::cancellable
+    /// [`with_fuel`]: RuntimeBuilder::with_fuel
+    /// [`enable_cache`]: RuntimeBuilder::enable_cache
+    /// [`with_pooling_allocator`]: RuntimeBuilder::with_pooling_allocator
+    /// [`parallel_compilation`]: RuntimeBuilder::parallel_compilation
+    /// [`with_backtraces`]: RuntimeBuilder::with_backtraces
+    /// [`with_debug_info`]: RuntimeBuilder::with_debug_info
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Captures what the guest writes to stdout instead of inheriting the host's, to be read with
+    /// [`Runtime::take_stdout`].
+    pub fn capture_stdout(mut self) -> Self {
+        self.capture_stdout = true;
+        self
+    }
+
+    /// Captures what the guest writes to stderr instead of inheriting the host's, to be read with
+    /// [`Runtime::take_stderr`].
+    pub fn capture_stderr(mut self) -> Self {
+        self.capture_stderr = true;
+        self
+    }
+
+    /// Replaces the guest's stdin with a pipe and returns the builder along with the pipe's host
+    /// end, so input can be fed to an interactive guest while it runs.
+    ///
+    /// Guest reads wait for bytes written to the returned stream, which buffers up to
+    /// [`STDIN_PIPE_BUFFER`](crate::STDIN_PIPE_BUFFER) bytes. Dropping it closes the guest's
+    /// stdin.
+    pub fn stdin_pipe(mut self) -> (Self, DuplexStream) {
+        let (host, guest) = tokio::io::duplex(STDIN_PIPE_BUFFER);
+        self.stdin = Some(AsyncStdinStream::new(AsyncReadStream::new(guest)));
+        (self, host)
+    }
+
+    /// Command-line arguments the guest sees through WASI, including the program name if it
+    /// expects one.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let args: Vec<String> = args.into_iter().map(|arg| arg.as_ref().into()).collect();
+        self.configure_wasi(move |wasi_ctx| {
+            for arg in &args {
+                wasi_ctx.arg(arg);
+            }
+        })
+    }
+
+    /// Sets an environment variable for the guest. The host's environment is not inherited unless
+    /// [`RuntimeBuilder::inherit_env`] is set, and variables set here take precedence over it.
+    pub fn env(self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.envs([(key, value)])
+    }
+
+    /// Sets several environment variables for the guest, see [`RuntimeBuilder::env`].
+    pub fn envs<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let vars: Vec<(String, String)> = vars
+            .into_iter()
+            .map(|(key, value)| (key.as_ref().into(), value.as_ref().into()))
+            .collect();
+        self.env_keys
+            .extend(vars.iter().map(|(key, _)| key.clone()));
+        self.configure_wasi(move |wasi_ctx| {
+            for (key, value) in &vars {
+                wasi_ctx.env(key, value);
+            }
+        })
+    }
+
+    /// Whether the guest sees the host process's environment variables, as they are when
+    /// [`RuntimeBuilder::build`] is called. Off by default, since the environment often holds
+    /// credentials and configuration guests shouldn't read.
+    ///
+    /// Variables set with [`RuntimeBuilder::env`] or [`RuntimeBuilder::envs`] override inherited
+    /// ones with the same name. Variables that aren't valid UTF-8 are skipped.
+    pub fn inherit_env(mut self, inherit: bool) -> Self {
+        self.inherit_env = inherit;
+        self
+    }
+
+    /// Makes the guest's clocks read `time` forever instead of following the system clock, so
+    /// runs that look at the time are reproducible.
+    ///
+    /// The wall clock reports `time` and the monotonic clock stays at zero.
+    pub fn with_fixed_time(self, time: SystemTime) -> Self {
+        self.configure_wasi(move |wasi_ctx| {
+            wasi_ctx.wall_clock(FixedClock::new(time));
+            wasi_ctx.monotonic_clock(FixedClock::new(time));
+        })
+    }
+
+    /// Feeds the guest's `wasi:random` interfaces from a PRNG seeded with `seed` instead of the
+    /// host's entropy, so runs that sample randomness are reproducible.
+    ///
+    /// This covers the secure and insecure random sources and the insecure seed. The insecure
+    /// ones are seeded from a value derived from `seed`, so they don't hand out the same bytes as
+    /// the secure source. It must never be used where the guest relies on unpredictable
+    /// randomness, e.g. for cryptography.
+    pub fn with_seeded_rng(self, seed: u64) -> Self {
+        let insecure_seed = seed ^ INSECURE_SEED_SALT;
+        self.configure_wasi(move |wasi_ctx| {
+            wasi_ctx.secure_random(StdRng::seed_from_u64(seed));
+            wasi_ctx.insecure_random(StdRng::seed_from_u64(insecure_seed));
+            wasi_ctx.insecure_random_seed(insecure_seed.into());
+        })
+    }
+
+    /// Whether the guest may use the host's network through `wasi:sockets`. Off by default.
+    ///
+    /// Allowing it is a broad grant: the guest can open TCP and UDP sockets to and from any
+    /// address the host can reach, including loopback and private networks, and resolve names
+    /// through the host's resolver. Only enable it for trusted guests or behind an external
+    /// firewall.
+    pub fn allow_network(self, allow: bool) -> Self {
+        self.configure_wasi(move |wasi_ctx| {
+            if allow {
+                wasi_ctx.inherit_network();
+            } else {
+                wasi_ctx.socket_addr_check(|_, _| false);
+            }
+            wasi_ctx.allow_ip_name_lookup(allow);
+        })
+    }
+
+    /// Configures the guest's WASI context directly, for anything the other builder methods don't
+    /// cover.
+    ///
+    /// `configure` runs for every store the runtime sets up, its own and those created by
+    /// [`Runtime::spawn_instance`], on top of the defaults (inherited stdio) and whatever earlier
+    /// builder calls set, and later calls layer over it in turn.
+    /// [`RuntimeBuilder::stdin_pipe`], [`RuntimeBuilder::capture_stdout`] and
+    /// [`RuntimeBuilder::capture_stderr`] take precedence over streams set here.
+    pub fn with_wasi_ctx<F>(self, configure: F) -> Self
+    where
+        F: Fn(&mut WasiCtxBuilder) + Send + Sync + 'static,
+    {
+        self.configure_wasi(configure)
+    }
+
+    /// Gives the guest access to the host directory `host_path`, mounted at `guest_path`.
+    ///
+    /// `dir_perms` controls what the guest may do with directories under it: [`DirPerms::READ`]
+    /// lists them and opens entries, [`DirPerms::MUTATE`] creates, renames and removes entries.
+    /// `file_perms` caps what files may be opened for: [`FilePerms::READ`] and
+    /// [`FilePerms::WRITE`]. Paths can't escape `host_path`. The directory is opened by
+    /// [`RuntimeBuilder::build`], which fails if it doesn't exist.
+    pub fn preopen_dir(
+        mut self,
+        host_path: impl Into<PathBuf>,
+        guest_path: impl Into<String>,
+        dir_perms: DirPerms,
+        file_perms: FilePerms,
+    ) -> Self {
+        self.preopens
+            .push((host_path.into(), guest_path.into(), dir_perms, file_perms));
+        self
+    }
+
+    /// Enables fuel metering and gives the store `fuel` units to start with.
+    ///
+    /// Every instantiation and call consumes fuel, roughly one unit per wasm instruction. When
+    /// it runs out the guest traps, which [`Runtime::classify_error`] reports as
+    /// [`crate::RuntimeError::OutOfFuel`]; top up with [`Runtime::add_fuel`] and retry on a new
+    /// instance, since the trapped one can't be entered again.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Enables Wasmtime's on-disk compilation cache, so compiling a component that was compiled
+    /// before (by any process using the same cache) just loads the cached code.
+    ///
+    /// `config` is a cache configuration file; `None` uses Wasmtime's default one, which lives
+    /// in the platform's config directory and is used with default settings if missing.
+    pub fn enable_cache(mut self, config: Option<PathBuf>) -> Self {
+        self.cache = Some(config);
+        self
+    }
+
+    /// Allocates instances from pools reserved up front instead of on demand, which makes
+    /// instantiating into fresh stores (see [`Runtime::spawn_instance`]) much cheaper on a hot
+    /// path. [`crate::default_pooling_config`] is a reasonable starting point.
+    ///
+    /// The tradeoff is that the pool reserves virtual memory for every slot when the engine is
+    /// created, and that instantiation fails once all slots are in use or when a component needs
+    /// more memories, tables or pages than a slot provides.
+    pub fn with_pooling_allocator(mut self, pooling: PoolingAllocationConfig) -> Self {
+        self.pooling = Some(pooling);
+        self
+    }
+
+    /// Caps how long a single poll of a guest call may run before it yields back to the executor.
+    ///
+    /// This enables epoch interruption and starts a background thread that advances the epoch
+    /// every `budget`. Whenever the deadline passes the guest yields and is rescheduled, so other
+    /// tasks on the same executor thread keep making progress. Unlike Wasmtime's
+    /// `fuel_async_yield_interval`, the budget is measured in wall-clock time and does not require
+    /// fuel metering, but the exact yield points are not deterministic.
+    pub fn poll_budget(mut self, budget: Duration) -> Self {
+        self.poll_budget = Some(budget);
+        self
+    }
+
+    /// Maximum stack space, in bytes, guest code may use before it traps with a stack overflow.
+    ///
+    /// The async fiber stack guest calls run on is grown to fit, with room left for host frames.
+    /// Call failures caused by an overflow are reported as [`crate::RuntimeError::StackOverflow`]
+    /// by [`Runtime::classify_error`].
+    pub fn max_wasm_stack(mut self, bytes: usize) -> Self {
+        self.max_wasm_stack = Some(bytes);
+        self
+    }
+
+    /// Pre-sizes the runtime's [`ResourceTable`](wasmtime::component::ResourceTable) for
+    /// `capacity` entries to avoid reallocating during bursts of resource creation. This is a
+    /// hint, not a limit: the table still grows past it.
+    pub fn resource_table_capacity(mut self, capacity: usize) -> Self {
+        self.resource_table_capacity = Some(capacity);
+        self
+    }
+
+    /// Admission check run by [`Runtime::load_component`] after a component compiles.
+    ///
+    /// Returning `Err(reason)` rejects the component with [`crate::RuntimeError::Rejected`].
+    /// The engine is passed along since the component type needs it to resolve its items.
+    /// The policy also gets the component's [`FootprintEstimate`], to bound the memory it
+    /// declares, except for [`Runtime::load_precompiled`] artifacts where the original bytes
+    /// are gone and it gets `None`.
+    pub fn validation_policy<F>(mut self, policy: F) -> Self
+    where
+        F: Fn(&Engine, &types::Component, Option<&FootprintEstimate>) -> Result<(), String>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.validation_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Maximum number of components [`Runtime::warm_many`] compiles at once. Defaults to the
+    /// available parallelism; zero is treated as one.
+    pub fn warm_concurrency(mut self, threads: usize) -> Self {
+        self.warm_concurrency = Some(NonZeroUsize::new(threads).unwrap_or(NonZeroUsize::MIN));
+        self
+    }
+
+    /// Whether Cranelift may compile a component's functions on several threads. Wasmtime
+    /// enables this by default.
+    pub fn parallel_compilation(mut self, parallel: bool) -> Self {
+        self.parallel_compilation = Some(parallel);
+        self
+    }
+
+    /// Caps parallel compilation at `threads` threads, e.g. to stay within a container's CPU
+    /// limit. By default it uses a thread per core; zero is treated as one.
+    ///
+    /// The runtime gets its own compilation thread pool, which [`Runtime::load_component`] and
+    /// the other compiling methods run on.
+    pub fn compilation_threads(mut self, threads: usize) -> Self {
+        self.compilation_threads = Some(threads.max(1));
+        self
+    }
+
+    /// Whether traps capture the guest's wasm backtrace, which [`Runtime::classify_error`] and
+    /// instantiation errors report through [`RuntimeError::backtrace`]. Wasmtime enables this by
+    /// default.
+    pub fn with_backtraces(mut self, backtraces: bool) -> Self {
+        self.backtraces = Some(backtraces);
+        self
+    }
+
+    /// Whether to keep DWARF debug info from guests, so backtraces show function names and
+    /// source locations and native debuggers can step through guest code. Off by default, since
+    /// it slows down compilation and grows the compiled code.
+    pub fn with_debug_info(mut self, debug_info: bool) -> Self {
+        self.debug_info = Some(debug_info);
+        self
+    }
+
+    /// Satisfies imports the host doesn't provide with default implementations, so components
+    /// built against a newer world keep working with an older host configuration.
+    ///
+    /// Shims are linked after WASI, global functions, the nested view and
+    /// [`RuntimeBuilder::configure_linker`], and only for
+    /// interfaces (or root functions) none of those defined. The ones that ended up active are
+    /// listed by [`Runtime::shimmed_imports`].
+    pub fn shim_missing_imports(mut self, shims: impl IntoIterator<Item = ImportShim>) -> Self {
+        self.shims.extend(shims);
+        self
+    }
+
+    /// Registers extra items on the linker that bindgen doesn't cover, e.g. raw host functions
+    /// added with `linker.root().func_wrap_async(..)`.
+    ///
+    /// `configure` runs after WASI, global functions and the nested view were added, and before
+    /// import shims. Calling this several times runs every closure in order.
+    pub fn configure_linker<F>(mut self, configure: F) -> Self
+    where
+        F: FnOnce(&mut Linker<RuntimeView<T>>) -> anyhow::Result<()> + Send + 'static,
+    {
+        self.linker_hooks.push(Box::new(configure));
+        self
+    }
+
+    /// Handles [`Runtime::call_named`] calls to exports the instance doesn't have, instead of
+    /// failing them.
+    pub fn export_fallback<F>(mut self, fallback: F) -> Self
+    where
+        F: Fn(&str, &[Val]) -> anyhow::Result<Vec<Val>> + Send + Sync + 'static,
+    {
+        self.export_fallback = Some(Arc::new(fallback));
+        self
+    }
+
+    /// Caps the total wall-clock time the runtime may be used for, counted from
+    /// [`RuntimeBuilder::build`]. Once it is spent, [`Runtime::call_named`] fails with
+    /// [`crate::RuntimeError::LifetimeExceeded`] without calling into the guest.
+    ///
+    /// A call already in progress is not interrupted; combine this with a per-call timeout for
+    /// that.
+    pub fn max_lifetime(mut self, lifetime: Duration) -> Self {
+        self.max_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Fails instantiations that run longer than `limit`, e.g. because of a slow start function,
+    /// with [`crate::RuntimeError::InstantiationTimedOut`] instead of letting them hang.
+    ///
+    /// This relies on epoch interruption, which the builder enables, and applies to
+    /// [`Runtime::instantiate_component`] and the helpers built on it. Unless a poll budget sets a
+    /// finer interval, the deadline is checked every 10ms, so it may be overrun by about that
+    /// much.
+    pub fn max_instantiation_time(mut self, limit: Duration) -> Self {
+        self.max_instantiation_time = Some(limit);
+        self
+    }
+
+    /// Limits how often guests may call the host function `name` of `interface`.
+    ///
+    /// Calls past the limit trap the guest with [`crate::RuntimeError::RateLimited`]. Every
+    /// runtime gets its own budget.
+    ///
+    /// # Limitations
+    ///
+    /// Only functions the crate dispatches itself can be limited: global host functions
+    /// ([`crate::register_global_host_fn`]) and import shims
+    /// ([`RuntimeBuilder::shim_missing_imports`]). Functions defined by the nested view, WASI, or
+    /// [`RuntimeBuilder::configure_linker`] are handed straight to Wasmtime's linker, which has no
+    /// way to look up or wrap a function once it is defined, so they can't be limited here. A
+    /// nested view that wants to limit one of its own functions has to count calls in that
+    /// function. Building fails with [`crate::RuntimeError::LinkerSetup`] if no global host
+    /// function or linked shim matches, rather than leaving the limit silently unenforced.
+    pub fn rate_limit_host_fn(
+        mut self,
+        interface: impl Into<String>,
+        name: impl Into<String>,
+        limit: RateLimit,
+    ) -> Self {
+        self.rate_limits
+            .insert(format!("{}#{}", interface.into(), name.into()), limit);
+        self
+    }
+
+    /// Quarantines a component once its named instances trapped `threshold` times within
+    /// `window`, making instantiating it fail with [`crate::RuntimeError::Quarantined`] for
+    /// `cooldown`.
+    ///
+    /// Components are identified by a hash of their compiled artifact, so recompiling the same
+    /// bytes doesn't escape the quarantine. Traps are counted for instances created with
+    /// [`Runtime::instantiate_named`] and called through [`Runtime::call_named`], and the count
+    /// starts over once a quarantine is imposed. Calls that fail without the guest trapping, e.g.
+    /// for a missing export, mistyped arguments or a timeout, are not counted.
+    pub fn quarantine_policy(
+        mut self,
+        threshold: u32,
+        window: Duration,
+        cooldown: Duration,
+    ) -> Self {
+        self.quarantine_policy = Some(QuarantinePolicy {
+            threshold: threshold.max(1),
+            window,
+            cooldown,
+        });
+        self
+    }
+
+    /// Lets guests report progress by importing [`crate::PROGRESS_INTERFACE`], e.g. to drive a
+    /// progress bar for a batch component.
+    ///
+    /// `callback` receives each report with the fraction clamped to `[0, 1]`.
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(f32, &str) + Send + 'static,
+    {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Serves `values` to guests that import `wasi:config/store`
+    /// ([`crate::CONFIG_STORE_INTERFACE`]), so configuration is delivered by the host instead of
+    /// baked into each component.
+    ///
+    /// `get` returns the value for a key, or none for keys not in `values`, and `get-all`
+    /// returns every pair sorted by key.
+    pub fn with_config_store(mut self, values: HashMap<String, String>) -> Self {
+        self.config_store = Some(values);
+        self
+    }
+
+    /// Fails guest calls made through [`Runtime::call_named`] that run longer than `timeout` with
+    /// [`crate::RuntimeError::Timeout`], so a guest stuck in a loop can't hang the host.
+    ///
+    /// Like [`RuntimeBuilder::max_instantiation_time`] this relies on epoch interruption, which
+    /// the builder enables, and the deadline is checked on every epoch tick. The instance that
+    /// timed out traps and can't be called again.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.call_timeout = Some(timeout);
+        self
+    }
+
+    /// Lets [`Runtime::call_cancellable`] interrupt guest calls when their token is cancelled.
+    ///
+    /// This enables epoch interruption, so a cancelled call fails on the next epoch tick even if
+    /// the guest never yields.
+    pub fn cancellable(mut self) -> Self {
+        self.cancellable = true;
+        self
+    }
+
+    /// Caps the size of each linear memory a guest instance may have, in bytes.
+    ///
+    /// Growing a memory past the cap traps the guest instead of returning failure to it, and
+    /// instantiating a component whose initial memory exceeds it fails.
+    pub fn max_memory_bytes(mut self, bytes: usize) -> Self {
+        self.max_memory_bytes = Some(bytes);
+        self
+    }
+
+    /// Caps the number of elements of each table a guest instance may have, with the same
+    /// semantics as [`RuntimeBuilder::max_memory_bytes`].
+    pub fn max_table_elements(mut self, elements: usize) -> Self {
+        self.max_table_elements = Some(elements);
+        self
+    }
+
+    /// Like [`RuntimeBuilder::build`], but first awaits [`NestedView::initialize`] so the nested
+    /// view can finish async setup before it is linked.
+    pub async fn build_async(mut self) -> Result<Runtime<T>, RuntimeError> {
+        if let Some(nested_view) = &mut self.nested_view {
+            nested_view.initialize().await?;
+        }
+        self.build()
+    }
+
+    pub fn build(mut self) -> Result<Runtime<T>, RuntimeError> {
+        let Some(mut nested_view) = self.nested_view.take() else {
+            return Err(anyhow!("a nested view is required to build a runtime").into());
+        };
+
+        let uses_epochs = self.uses_epochs();
+        if let Some(config) = &self.config {
+            if !supports_async(config).map_err(RuntimeError::EngineCreation)? {
+                return Err(RuntimeError::SyncAsyncMismatch);
+            }
+        }
+        let shared_engine = self.engine.is_some();
+        let engine =
+            traced!("create_engine"; self.create_engine()).map_err(RuntimeError::EngineCreation)?;
+
+        let (linker, shimmed_imports, linker_timings) =
+            traced!("linker_setup"; self.create_linker(&engine, &mut nested_view))?;
+
+        let inherited_env = match self.inherit_env {
+            true => std::env::vars_os()
+                .filter_map(|(key, value)| {
+                    Some((key.into_string().ok()?, value.into_string().ok()?))
+                })
+                .filter(|(key, _)| !self.env_keys.contains(key))
+                .collect(),
+            false => Vec::new(),
+        };
+        let store_settings = StoreSettings {
+            shared_engine,
+            wasi: self.wasi,
+            stdin_piped: self.stdin.is_some(),
+            capture_stdout: self.capture_stdout,
+            capture_stderr: self.capture_stderr,
+            inherited_env,
+            preopens: self.preopens,
+            resource_table_capacity: self.resource_table_capacity,
+            max_memory_bytes: self.max_memory_bytes,
+            max_table_elements: self.max_table_elements,
+        };
+
+        let mut wasi_ctx = store_settings.wasi_ctx()?;
+        if let Some(stdin) = self.stdin {
+            wasi_ctx.stdin(stdin);
+        }
+        let captured_stdout = self.capture_stdout.then(CapturedPipe::new);
+        if let Some(captured) = &captured_stdout {
+            wasi_ctx.stdout(captured.pipe());
+        }
+        let captured_stderr = self.capture_stderr.then(CapturedPipe::new);
+        if let Some(captured) = &captured_stderr {
+            wasi_ctx.stderr(captured.pipe());
+        }
+
+        let runtime_view = RuntimeView::with_ctx(nested_view, wasi_ctx.build());
+        let mut store = store_settings.store(&engine, runtime_view)?;
+        if let Some(fuel) = self.fuel {
+            store.set_fuel(fuel)?;
+        }
+
+        let compilation_pool = match self.compilation_threads {
+            Some(threads) => Some(Arc::new(
+                ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|index| format!("its-wasmtime-compile-{index}"))
+                    .build()
+                    .context("failed to start compilation threads")?,
+            )),
+            None => None,
+        };
+
+        let deadline = Deadline::default();
+        let epoch_ticker = if uses_epochs {
+            store.set_epoch_deadline(1);
+            let on_deadline =
+                epoch::deadline_callback(self.poll_budget.is_some(), deadline.clone());
+            store.epoch_deadline_callback(move |_| on_deadline());
+            let interval = self.poll_budget.unwrap_or(DEADLINE_CHECK_INTERVAL);
+            Some(EpochTicker::spawn(engine.clone(), interval)?)
+        } else {
+            None
+        };
+
+        Ok(Runtime {
+            engine,
+            linker,
+            store,
+            validation_policy: self.validation_policy,
+            warm_concurrency: self.warm_concurrency,
+            compilation_pool,
+            max_wasm_stack: self.max_wasm_stack.unwrap_or(DEFAULT_MAX_WASM_STACK),
+            shimmed_imports,
+            named_instances: Default::default(),
+            export_fallback: self.export_fallback,
+            linker_timings,
+            created_at: Instant::now(),
+            max_lifetime: self.max_lifetime,
+            events: EventPublisher::new(),
+            max_instantiation_time: self.max_instantiation_time,
+            call_timeout: self.call_timeout,
+            deadline,
+            quarantine: self.quarantine_policy.map(QuarantineTracker::new),
+            captured_stdout,
+            captured_stderr,
+            fuel_added: self.fuel,
+            initial_fuel: self.fuel,
+            epoch_ticker,
+            store_settings,
+        })
+    }
+}
+
+impl<T> RuntimeBuilder<T>
+where
+    T: NestedView,
+{
+    fn configure_wasi(
+        mut self,
+        setting: impl Fn(&mut WasiCtxBuilder) + Send + Sync + 'static,
+    ) -> Self {
+        self.wasi.push(Arc::new(setting));
+        self
+    }
+
+    fn uses_epochs(&self) -> bool {
+        self.poll_budget.is_some()
+            || self.max_instantiation_time.is_some()
+            || self.call_timeout.is_some()
+            || self.cancellable
+    }
+
+    fn create_linker(
+        &mut self,
+        engine: &Engine,
+        nested_view: &mut T,
+    ) -> Result<(Linker<RuntimeView<T>>, Vec<String>, LinkerTimings), RuntimeError> {
+        let linker_start = Instant::now();
+        let mut linker = Linker::new(engine);
+
+        let wasi_start = Instant::now();
+        if self.with_wasi {
+            wasmtime_wasi::add_to_linker_async(&mut linker).map_err(RuntimeError::LinkerSetup)?;
+            command::add_command_capture_to_linker(&mut linker)
+                .map_err(RuntimeError::LinkerSetup)?;
+        }
+        if self.with_wasi_http {
+            match self.with_wasi {
+                true => wasmtime_wasi_http::proxy::add_only_http_to_linker(&mut linker),
+                false => wasmtime_wasi_http::proxy::add_to_linker(&mut linker),
+            }
+            .map_err(RuntimeError::LinkerSetup)?;
+        }
+        let wasi = wasi_start.elapsed();
+
+        let global_fns = global::add_global_host_fns_to_linker(&mut linker, &self.rate_limits)
+            .map_err(RuntimeError::LinkerSetup)?;
+        if let Some(callback) = self.on_progress.take() {
+            progress::add_progress_to_linker(&mut linker, callback)
+                .map_err(RuntimeError::LinkerSetup)?;
+        }
+        if let Some(values) = self.config_store.take() {
+            config_store::add_config_store_to_linker(&mut linker, values)
+                .map_err(RuntimeError::LinkerSetup)?;
+        }
+
+        let nested_view_start = Instant::now();
+        nested_view
+            .add_all_to_linker(&mut linker)
+            .map_err(RuntimeError::LinkerSetup)?;
+        let nested_view_time = nested_view_start.elapsed();
+
+        for configure in std::mem::take(&mut self.linker_hooks) {
+            configure(&mut linker).map_err(RuntimeError::LinkerSetup)?;
+        }
+
+        let shims = std::mem::take(&mut self.shims);
+        let shimmed_imports = shim::add_shims_to_linker(&mut linker, shims, &self.rate_limits)
+            .map_err(RuntimeError::LinkerSetup)?;
+        self.rate_limits
+            .ensure_applied(global_fns.iter().chain(&shimmed_imports))
+            .map_err(RuntimeError::LinkerSetup)?;
+
+        let linker_timings = LinkerTimings {
+            wasi,
+            nested_view: nested_view_time,
+            total: linker_start.elapsed(),
+        };
+
+        Ok((linker, shimmed_imports, linker_timings))
+    }
+
+    fn create_engine(&mut self) -> anyhow::Result<Engine> {
+        if let Some(engine) = self.engine.take() {
+            anyhow::ensure!(
+                self.config.is_none()
+                    && self.poll_budget.is_none()
+                    && self.max_wasm_stack.is_none()
+                    && self.max_instantiation_time.is_none()
+                    && self.call_timeout.is_none()
+                    && !self.cancellable
+                    && self.fuel.is_none()
+                    && self.cache.is_none()
+                    && self.pooling.is_none()
+                    && self.parallel_compilation.is_none()
+                    && self.backtraces.is_none()
+                    && self.debug_info.is_none(),
+                "engine options can't be applied to a shared engine"
+            );
+            Component::new(&engine, "(component)")
+                .context("the shared engine doesn't support the component model")?;
+            return Ok(engine);
+        }
+
+        let mut config = self.config.take().unwrap_or_else(crate::default_config);
+        config.wasm_component_model(true);
+        config.epoch_interruption(self.uses_epochs());
+        config.consume_fuel(self.fuel.is_some());
+        match &self.cache {
+            Some(Some(path)) => {
+                config.cache_config_load(path)?;
+            }
+            Some(None) => {
+                config.cache_config_load_default()?;
+            }
+            None => {}
+        }
+        if let Some(parallel) = self.parallel_compilation {
+            config.parallel_compilation(parallel);
+        }
+        if let Some(backtraces) = self.backtraces {
+            config.wasm_backtrace(backtraces);
+        }
+        if let Some(debug_info) = self.debug_info {
+            config.debug_info(debug_info);
+        }
+        if let Some(pooling) = self.pooling.take() {
+            config.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling));
+        }
+        if let Some(bytes) = self.max_wasm_stack {
+            config.max_wasm_stack(bytes);
+            config.async_stack_size(bytes + HOST_STACK_HEADROOM);
+        }
+
+        Engine::new(&config)
+    }
+}
+
+/// Whether engines created from `config` have async support, which WASI and every async host
+/// function need.
+///
+/// Wasmtime doesn't expose a config's settings, so a throwaway engine is created from a copy of
+/// it with fuel enabled, and asked to yield on fuel, which fails without async support.
+fn supports_async(config: &Config) -> anyhow::Result<bool> {
+    let mut probe = config.clone();
+    probe
+        .consume_fuel(true)
+        .allocation_strategy(InstanceAllocationStrategy::OnDemand)
+        .disable_cache();
+    let engine = Engine::new(&probe)?;
+    Ok(Store::new(&engine, ())
+        .fuel_async_yield_interval(None)
+        .is_ok())
+}
+
+impl<T> Default for RuntimeBuilder<T>
+where
+    T: NestedView,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod builder_test {
+    use crate::{test_support::EmptyView, RuntimeBuilder};
+
+    #[test]
+    fn it_requires_a_nested_view() {
+        assert!(RuntimeBuilder::<EmptyView>::new().build().is_err());
+    }
+
+    #[test]
+    fn it_defaults_to_the_plain_runtime() {
+        let runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .build()
+            .expect("Failed to build runtime");
+
+        assert!(runtime.shimmed_imports().is_empty());
+        assert!(runtime.check_lifetime().is_ok());
+        assert_eq!(runtime.max_wasm_stack, 512 * 1024);
+        assert!(runtime.epoch_ticker.is_none());
+    }
+}
+
+#[cfg(test)]
+mod with_config_test {
+    use wasmtime::{Config, OptLevel};
+
+    use crate::{default_config, test_support::EmptyView, RuntimeBuilder, RuntimeError};
+
+    #[tokio::test]
+    async fn it_builds_on_a_custom_config() {
+        let mut config = default_config();
+        config.cranelift_opt_level(OptLevel::None);
+        config.wasm_component_model(false);
+
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_config(config)
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component("(component)")
+            .expect("the component model stays enabled");
+        runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+    }
+
+    #[test]
+    fn it_rejects_sync_configs() {
+        let mut config = Config::new();
+        config.wasm_component_model(true);
+
+        let err = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_config(config)
+            .configure_linker(|linker| {
+                linker
+                    .root()
+                    .func_wrap_async("slow", |_, (): ()| Box::new(async { Ok(()) }))
+            })
+            .build()
+            .err()
+            .expect("a config without async support should be rejected");
+        assert!(matches!(err, RuntimeError::SyncAsyncMismatch));
+    }
+
+    #[test]
+    fn it_reports_invalid_configs() {
+        let mut config = default_config();
+        config.max_wasm_stack(0);
+
+        let err = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_config(config)
+            .build()
+            .err()
+            .expect("a zero stack should be rejected");
+        assert!(matches!(err, RuntimeError::EngineCreation(_)));
+    }
+}
+
+#[cfg(test)]
+mod wasi_args_test {
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_passes_args_to_the_guest() {
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .args(["cli_echo", "--name", "its wasmtime"])
+            .capture_stdout()
+            .build()
+            .expect("Failed to build runtime");
+
+        run_guest(&mut runtime, "cli_echo")
+            .await
+            .expect("command failed");
+
+        assert_eq!(runtime.take_stdout(), b"cli_echo\n--name\nits wasmtime\n");
+    }
+}
+
+#[cfg(test)]
+mod wasi_env_test {
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_passes_env_vars_to_the_guest() {
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .env("GREETING", "hello")
+            .envs([("LEVEL", "debug"), ("TENANT", "acme")])
+            .capture_stdout()
+            .build()
+            .expect("Failed to build runtime");
+
+        run_guest(&mut runtime, "cli_echo")
+            .await
+            .expect("command failed");
+
+        assert_eq!(
+            runtime.take_stdout(),
+            b"GREETING=hello\nLEVEL=debug\nTENANT=acme\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod initialize_test {
+    use std::time::Duration;
+
+    use wasmtime::component::{Linker, Val};
+    use wasmtime_wasi::async_trait;
+
+    use crate::{NestedView, RuntimeBuilder, RuntimeView};
+
+    const CALLS_CONNECTION: &str = r#"
+        (component
+            (import "its:test/db" (instance $db
+                (export "connection-id" (func (result u32)))))
+            (core func $connection-id (canon lower (func $db "connection-id")))
+            (core module $m
+                (import "host" "connection-id" (func $connection-id (result i32)))
+                (func (export "run") (result i32) (call $connection-id)))
+            (core instance $i (instantiate $m
+                (with "host" (instance (export "connection-id" (func $connection-id))))))
+            (func (export "run") (result u32) (canon lift (core func $i "run")))
+        )
+    "#;
+
+    struct DbView {
+        connection: Option<u32>,
+    }
+
+    #[async_trait]
+    impl NestedView for DbView {
+        async fn initialize(&mut self) -> anyhow::Result<()> {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.connection = Some(7);
+            Ok(())
+        }
+
+        fn add_all_to_linker(
+            &mut self,
+            linker: &mut Linker<RuntimeView<Self>>,
+        ) -> anyhow::Result<()> {
+            anyhow::ensure!(self.connection.is_some(), "not connected");
+
+            linker
+                .instance("its:test/db")?
+                .func_wrap("connection-id", |store, (): ()| {
+                    Ok((store.data().nested_view.connection.unwrap(),))
+                })
+        }
+    }
+
+    #[tokio::test]
+    async fn it_initializes_before_linking() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(DbView { connection: None })
+            .build_async()
+            .await
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(CALLS_CONNECTION)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        let results = runtime
+            .call_named("plugin", "run", &[])
+            .await
+            .expect("failed to call run");
+        assert_eq!(results, [Val::U32(7)]);
+    }
+
+    #[test]
+    fn it_skips_initialization_when_built_synchronously() {
+        let result = RuntimeBuilder::new()
+            .nested_view(DbView { connection: None })
+            .build();
+        assert!(result.is_err());
+    }
+}
+
+#[cfg(test)]
+mod inherit_env_test {
+    use std::process::Command;
+
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    const VAR: &str = "ITS_WASMTIME_INHERIT_ENV_TEST";
+
+    async fn guest_env(inherit: bool) -> String {
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .inherit_env(inherit)
+            .env("GREETING", "hello")
+            .capture_stdout()
+            .build()
+            .expect("Failed to build runtime");
+
+        run_guest(&mut runtime, "cli_echo")
+            .await
+            .expect("command failed");
+
+        String::from_utf8(runtime.take_stdout()).unwrap()
+    }
+
+    /// Setting a variable in the test process would race with other tests reading the
+    /// environment, so the checks run in a copy of the test binary started with `VAR` set.
+    #[test]
+    fn it_inherits_the_host_env_only_when_asked() {
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "builder::inherit_env_test::check_inherited_env",
+                "--ignored",
+            ])
+            .env(VAR, "visible")
+            .output()
+            .expect("failed to run the test binary");
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{stdout}");
+        assert!(stdout.contains("test result: ok. 1 passed"), "{stdout}");
+    }
+
+    #[tokio::test]
+    #[ignore = "run by it_inherits_the_host_env_only_when_asked with the variable set"]
+    async fn check_inherited_env() {
+        assert_eq!(std::env::var(VAR).as_deref(), Ok("visible"));
+
+        let inherited = guest_env(true).await;
+        assert!(inherited
+            .lines()
+            .any(|line| line == "ITS_WASMTIME_INHERIT_ENV_TEST=visible"));
+        assert!(inherited.lines().any(|line| line == "GREETING=hello"));
+
+        let isolated = guest_env(false).await;
+        assert!(!isolated.contains(VAR));
+        assert!(isolated.lines().any(|line| line == "GREETING=hello"));
+    }
+}
+
+#[cfg(test)]
+mod fixed_time_test {
+    use std::time::{Duration, SystemTime};
+
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    async fn print_time(time: SystemTime) -> Vec<u8> {
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .with_fixed_time(time)
+            .capture_stdout()
+            .build()
+            .expect("Failed to build runtime");
+
+        run_guest(&mut runtime, "print_time")
+            .await
+            .expect("command failed");
+
+        runtime.take_stdout()
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_fixed_time() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let first = print_time(time).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let second = print_time(time).await;
+
+        assert_eq!(first, b"1700000000000000000\n");
+        assert_eq!(first, second);
+    }
+}
+
+#[cfg(test)]
+mod seeded_rng_test {
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    async fn print_random(seed: u64) -> Vec<u8> {
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .with_seeded_rng(seed)
+            .capture_stdout()
+            .build()
+            .expect("Failed to build runtime");
+
+        run_guest(&mut runtime, "print_random")
+            .await
+            .expect("command failed");
+
+        runtime.take_stdout()
+    }
+
+    #[tokio::test]
+    async fn it_repeats_seeded_bytes() {
+        let first = print_random(7).await;
+        let second = print_random(7).await;
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+
+        assert_ne!(first, print_random(8).await);
+    }
+}
+
+#[cfg(test)]
+mod allow_network_test {
+    use tokio::net::TcpListener;
+
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    async fn connect(builder: RuntimeBuilder<EmptyView>) -> Result<(), ()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+
+        let mut runtime = builder
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .args(["tcp_connect", &port])
+            .capture_stderr()
+            .build()
+            .expect("Failed to build runtime");
+
+        // A failed connection makes the guest exit with a failure status.
+        run_guest(&mut runtime, "tcp_connect").await
+    }
+
+    #[tokio::test]
+    async fn it_denies_the_network_by_default() {
+        assert!(connect(RuntimeBuilder::new()).await.is_err());
+        assert!(connect(RuntimeBuilder::new().allow_network(false))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn it_allows_the_network_when_enabled() {
+        connect(RuntimeBuilder::new().allow_network(true))
+            .await
+            .expect("guest failed to connect");
+    }
+}
+
+#[cfg(test)]
+mod with_wasi_ctx_test {
+    use wasmtime_wasi::pipe::MemoryOutputPipe;
+
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_overrides_stdio() {
+        let stdout = MemoryOutputPipe::new(1024);
+        let stderr = MemoryOutputPipe::new(1024);
+
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .with_wasi_ctx({
+                let (stdout, stderr) = (stdout.clone(), stderr.clone());
+                move |wasi_ctx| {
+                    wasi_ctx.stdout(stdout.clone()).stderr(stderr.clone());
+                }
+            })
+            .build()
+            .expect("Failed to build runtime");
+
+        // The guest exits with a failure status, which surfaces as an error.
+        let _ = run_guest(&mut runtime, "command_output").await;
+
+        assert_eq!(&stdout.contents()[..], b"to stdout\n");
+        assert_eq!(&stderr.contents()[..], b"to stderr\n");
+    }
+}
+
+#[cfg(test)]
+mod wasi_http_test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_lets_the_guest_fetch() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind listener");
+        let authority = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+                )
+                .await
+                .unwrap();
+        });
+
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .with_wasi_http()
+            .nested_view(EmptyView)
+            .args(["http_fetch", authority.as_str()])
+            .capture_stdout()
+            .build()
+            .expect("Failed to build runtime");
+
+        run_guest(&mut runtime, "http_fetch")
+            .await
+            .expect("command failed");
+
+        server.await.unwrap();
+        assert_eq!(runtime.take_stdout(), b"hello");
+    }
+}
+
+#[cfg(test)]
+mod preopen_dir_test {
+    use std::fs;
+
+    use wasmtime_wasi::{DirPerms, FilePerms};
+
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_lets_the_guest_write_files() {
+        let host_dir =
+            std::env::temp_dir().join(format!("its-wasmtime-preopen-{}", std::process::id()));
+        fs::create_dir_all(&host_dir).expect("failed to create host directory");
+
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .preopen_dir(&host_dir, "/data", DirPerms::all(), FilePerms::all())
+            .build()
+            .expect("Failed to build runtime");
+
+        run_guest(&mut runtime, "write_file")
+            .await
+            .expect("command failed");
+
+        let written = fs::read_to_string(host_dir.join("hello.txt"));
+        fs::remove_dir_all(&host_dir).expect("failed to clean up host directory");
+        assert_eq!(
+            written.expect("guest did not write the file"),
+            "hello from the guest"
+        );
+    }
+
+    #[test]
+    fn it_fails_for_missing_directories() {
+        let result = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .preopen_dir("./does/not/exist", "/data", DirPerms::READ, FilePerms::READ)
+            .build();
+        assert!(result.is_err());
+    }
+}
+
+#[cfg(test)]
+mod configure_linker_test {
+    use wasmtime::component::Val;
+
+    use crate::{test_support::EmptyView, RuntimeBuilder};
+
+    const CALLS_DOUBLE: &str = r#"
+        (component
+            (import "its:test/extra" (instance $extra
+                (export "double" (func (param "x" u32) (result u32)))))
+            (core func $double (canon lower (func $extra "double")))
+            (core module $m
+                (import "host" "double" (func $double (param i32) (result i32)))
+                (func (export "run") (result i32) (call $double (i32.const 21))))
+            (core instance $i (instantiate $m
+                (with "host" (instance (export "double" (func $double))))))
+            (func (export "run") (result u32) (canon lift (core func $i "run")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_links_custom_functions() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .configure_linker(|linker| {
+                linker
+                    .instance("its:test/extra")?
+                    .func_wrap_async("double", |_, (x,): (u32,)| {
+                        Box::new(async move { Ok((x * 2,)) })
+                    })
+            })
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(CALLS_DOUBLE)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        let results = runtime
+            .call_named("plugin", "run", &[])
+            .await
+            .expect("failed to call run");
+        assert_eq!(results, [Val::U32(42)]);
+    }
+}
+
+#[cfg(test)]
+mod store_limits_test {
+    use crate::{test_support::EmptyView, RuntimeBuilder};
+
+    const GROWS_MEMORY: &str = r#"
+        (component
+            (core module $m
+                (memory 1)
+                (func (export "grow") (param i32) (result i32)
+                    (memory.grow (local.get 0))))
+            (core instance $i (instantiate $m))
+            (func (export "grow") (param "pages" u32) (result s32)
+                (canon lift (core func $i "grow")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_caps_memory_growth() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .max_memory_bytes(2 * 64 * 1024)
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(GROWS_MEMORY)
+            .expect("failed to compile component");
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let grow = instance
+            .get_typed_func::<(u32,), (i32,)>(&mut runtime.store, "grow")
+            .expect("missing grow export");
+
+        let (previous,) = grow
+            .call_async(&mut runtime.store, (1,))
+            .await
+            .expect("growing within the cap should succeed");
+        grow.post_return_async(&mut runtime.store)
+            .await
+            .expect("failed to post return");
+        assert_eq!(previous, 1);
+
+        assert!(grow.call_async(&mut runtime.store, (1,)).await.is_err());
+    }
+}
+
+#[cfg(test)]
+mod cache_test {
+    use std::fs;
+
+    use crate::{test_support::EmptyView, RuntimeBuilder};
+
+    #[test]
+    fn it_compiles_with_the_cache_enabled() {
+        let dir = std::env::temp_dir().join(format!("its-wasmtime-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("failed to create cache directory");
+        let config = dir.join("cache.toml");
+        fs::write(
+            &config,
+            format!(
+                "[cache]\nenabled = true\ndirectory = {:?}\n",
+                dir.join("artifacts")
+            ),
+        )
+        .expect("failed to write cache config");
+
+        for _ in 0..2 {
+            let runtime = RuntimeBuilder::new()
+                .nested_view(EmptyView)
+                .enable_cache(Some(config.clone()))
+                .build()
+                .expect("Failed to build runtime");
+            runtime
+                .load_component("(component (core module))")
+                .expect("failed to compile component");
+        }
+
+        fs::remove_dir_all(&dir).expect("failed to clean up cache directory");
+    }
+}
+
+#[cfg(test)]
+mod pooling_test {
+    use wasmtime::component::Val;
+
+    use crate::{default_pooling_config, test_support::EmptyView, RuntimeBuilder};
+
+    const WITH_MEMORY: &str = r#"
+        (component
+            (core module $m
+                (memory 1)
+                (func (export "f") (result i32)
+                    (i32.store (i32.const 0) (i32.const 42))
+                    (i32.load (i32.const 0))))
+            (core instance $i (instantiate $m))
+            (func (export "answer") (result u32) (canon lift (core func $i "f")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_instantiates_many_times_from_the_pool() {
+        let runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_pooling_allocator(default_pooling_config())
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(WITH_MEMORY)
+            .expect("failed to compile component");
+
+        for _ in 0..500 {
+            let mut instance = runtime
+                .spawn_instance(&component, EmptyView)
+                .await
+                .expect("failed to instantiate from the pool");
+            let results = instance
+                .call("answer", &[])
+                .await
+                .expect("failed to call answer");
+            assert_eq!(results, [Val::U32(42)]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod compilation_threads_test {
+    use crate::{
+        test_support::{answer, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_compiles_on_a_capped_pool() {
+        let runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .compilation_threads(2)
+            .build()
+            .expect("Failed to build runtime");
+
+        runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+        let source = answer(42);
+        let warmed = runtime
+            .warm_many(&[source.as_bytes(), source.as_bytes()])
+            .await;
+        assert_eq!(warmed.expect("failed to warm components").len(), 2);
+    }
+
+    #[test]
+    fn it_compiles_serially() {
+        let runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .parallel_compilation(false)
+            .build()
+            .expect("Failed to build runtime");
+
+        runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+    }
+}
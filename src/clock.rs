@@ -0,0 +1,41 @@
+use std::time::{Duration, SystemTime};
+
+use wasmtime_wasi::{HostMonotonicClock, HostWallClock};
+
+/// Clock that always reports the same instant, for reproducible guest runs.
+///
+/// The wall clock reads as the configured time and the monotonic clock stays at zero.
+pub(crate) struct FixedClock {
+    since_epoch: Duration,
+}
+
+impl FixedClock {
+    /// Times before the Unix epoch are clamped to it, since WASI can't represent them.
+    pub(crate) fn new(time: SystemTime) -> Self {
+        Self {
+            since_epoch: time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl HostWallClock for FixedClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    fn now(&self) -> Duration {
+        self.since_epoch
+    }
+}
+
+impl HostMonotonicClock for FixedClock {
+    fn resolution(&self) -> u64 {
+        1
+    }
+
+    fn now(&self) -> u64 {
+        0
+    }
+}
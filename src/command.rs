@@ -0,0 +1,179 @@
+use std::time::{Duration, Instant};
+
+use wasmtime::component::{Component, Linker, Resource};
+use wasmtime_wasi::{
+    bindings::{
+        cli::{stderr, stdout},
+        Command,
+    },
+    pipe::MemoryOutputPipe,
+    OutputStream,
+};
+
+use crate::{NestedView, Runtime, RuntimeError, RuntimeView};
+
+/// Bytes captured per stream by [`Runtime::run_command_captured`]. Writes past it fail in the
+/// guest.
+pub const COMMAND_CAPTURE_LIMIT: usize = 16 * 1024 * 1024;
+
+/// The outcome of running a command component, like [`std::process::Output`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// `0` on success. WASI 0.2 commands can only report success or failure, so any failure
+    /// is `1` unless the guest exited with an explicit code.
+    pub exit_code: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub duration: Duration,
+}
+
+/// Where [`Runtime::run_command_captured`] sends the guest's stdout and stderr while a command
+/// runs, in place of the streams the WASI context was built with.
+pub(crate) struct CommandCapture {
+    stdout: MemoryOutputPipe,
+    stderr: MemoryOutputPipe,
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Runs a `wasi:cli/command` component to completion, capturing its stdout and stderr.
+    ///
+    /// Requires a runtime built with WASI. The command is instantiated like any other component,
+    /// so quarantine, the instantiation deadline and events apply, and it sees the runtime's WASI
+    /// context (args, env, preopens, clocks, ...) except that its stdout and stderr go to the
+    /// captured output for the duration of the run.
+    pub async fn run_command_captured(
+        &mut self,
+        component: &Component,
+    ) -> Result<CommandOutput, RuntimeError> {
+        let capture = CommandCapture {
+            stdout: MemoryOutputPipe::new(COMMAND_CAPTURE_LIMIT),
+            stderr: MemoryOutputPipe::new(COMMAND_CAPTURE_LIMIT),
+        };
+        let (stdout, stderr) = (capture.stdout.clone(), capture.stderr.clone());
+        self.store.data_mut().command_capture = Some(capture);
+
+        let start = Instant::now();
+        let result = self.run_command(component).await;
+        let duration = start.elapsed();
+        self.store.data_mut().command_capture = None;
+
+        let exit_code = match result.map_err(|err| self.classify_error(err)) {
+            Ok(Ok(())) => 0,
+            Ok(Err(())) => 1,
+            Err(RuntimeError::Exit(code)) => code,
+            Err(err) => return Err(err),
+        };
+
+        Ok(CommandOutput {
+            exit_code,
+            stdout: stdout.contents().to_vec(),
+            stderr: stderr.contents().to_vec(),
+            duration,
+        })
+    }
+
+    async fn run_command(&mut self, component: &Component) -> anyhow::Result<Result<(), ()>> {
+        let instance = self.instantiate_component(component).await?;
+        let command = Command::new(&mut self.store, &instance)?;
+        command.wasi_cli_run().call_run(&mut self.store).await
+    }
+}
+
+/// Replaces WASI's `get-stdout` and `get-stderr` with versions that hand out the
+/// [`CommandCapture`] pipes while [`Runtime::run_command_captured`] runs, and the context's own
+/// streams otherwise.
+pub(crate) fn add_command_capture_to_linker<T>(
+    linker: &mut Linker<RuntimeView<T>>,
+) -> anyhow::Result<()>
+where
+    T: NestedView,
+{
+    linker.allow_shadowing(true);
+    let result = define_capturing_streams(linker);
+    linker.allow_shadowing(false);
+    result
+}
+
+fn define_capturing_streams<T>(linker: &mut Linker<RuntimeView<T>>) -> anyhow::Result<()>
+where
+    T: NestedView,
+{
+    linker.instance("wasi:cli/stdout@0.2.0")?.func_wrap(
+        "get-stdout",
+        |mut store, (): ()| -> anyhow::Result<(Resource<OutputStream>,)> {
+            let view = store.data_mut();
+            let stream = match &view.command_capture {
+                Some(capture) => {
+                    let stream: OutputStream = Box::new(capture.stdout.clone());
+                    view.table.push(stream)?
+                }
+                None => stdout::Host::get_stdout(view)?,
+            };
+            Ok((stream,))
+        },
+    )?;
+    linker.instance("wasi:cli/stderr@0.2.0")?.func_wrap(
+        "get-stderr",
+        |mut store, (): ()| -> anyhow::Result<(Resource<OutputStream>,)> {
+            let view = store.data_mut();
+            let stream = match &view.command_capture {
+                Some(capture) => {
+                    let stream: OutputStream = Box::new(capture.stderr.clone());
+                    view.table.push(stream)?
+                }
+                None => stderr::Host::get_stderr(view)?,
+            };
+            Ok((stream,))
+        },
+    )
+}
+
+#[cfg(test)]
+mod command_output_test {
+    use crate::{
+        runtime,
+        test_support::{load_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_captures_command_output() {
+        let mut runtime = runtime(true, EmptyView).expect("Failed to build runtime");
+
+        let component = load_guest(&runtime.engine, "command_output");
+
+        let output = runtime
+            .run_command_captured(&component)
+            .await
+            .expect("failed to run command");
+
+        assert_eq!(output.exit_code, 1);
+        assert_eq!(output.stdout, b"to stdout\n");
+        assert_eq!(output.stderr, b"to stderr\n");
+        assert!(!output.duration.is_zero());
+    }
+
+    #[tokio::test]
+    async fn it_keeps_the_configured_wasi_context() {
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .args(["cli_echo", "--verbose"])
+            .env("TENANT", "acme")
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = load_guest(&runtime.engine, "cli_echo");
+
+        let output = runtime
+            .run_command_captured(&component)
+            .await
+            .expect("failed to run command");
+
+        assert_eq!(output.exit_code, 0);
+        assert_eq!(output.stdout, b"cli_echo\n--verbose\nTENANT=acme\n");
+    }
+}
@@ -0,0 +1,300 @@
+use anyhow::anyhow;
+use wasmtime::component::Linker;
+
+use crate::{NestedView, RuntimeView};
+
+/// A host interface implementation that can be one field of a view composed with
+/// [`nested_view!`](crate::nested_view), or one member of a tuple view such as `(A, B)`.
+///
+/// `get` projects the composed runtime view onto this implementation. It has the shape bindgen's
+/// generated `add_to_linker` functions expect, so implementations usually just forward it:
+///
+/// ```ignore
+/// impl<V: NestedView> HostInterface<V> for MyHost {
+///     fn add_to_linker(
+///         &mut self,
+///         linker: &mut Linker<RuntimeView<V>>,
+///         get: fn(&mut RuntimeView<V>) -> &mut Self,
+///     ) -> anyhow::Result<()> {
+///         my_host::add_to_linker(linker, get)
+///     }
+/// }
+/// ```
+pub trait HostInterface<V: NestedView>: Send + Sized {
+    fn add_to_linker(
+        &mut self,
+        linker: &mut Linker<RuntimeView<V>>,
+        get: fn(&mut RuntimeView<V>) -> &mut Self,
+    ) -> anyhow::Result<()>;
+}
+
+/// Registers several bindgen generated interfaces whose host traits are implemented by the nested
+/// view, collecting their errors instead of stopping at the first one.
+///
+/// ```ignore
+/// impl NestedView for MyView {
+///     fn add_all_to_linker(
+///         &mut self,
+///         linker: &mut Linker<RuntimeView<Self>>,
+///     ) -> anyhow::Result<()> {
+///         InterfaceLinker::new(linker)
+///             .link(http::add_to_linker)
+///             .link(storage::add_to_linker)
+///             .finish()
+///     }
+/// }
+/// ```
+pub struct InterfaceLinker<'a, V: NestedView> {
+    linker: &'a mut Linker<RuntimeView<V>>,
+    errors: Vec<anyhow::Error>,
+}
+
+impl<'a, V: NestedView> InterfaceLinker<'a, V> {
+    pub fn new(linker: &'a mut Linker<RuntimeView<V>>) -> Self {
+        Self {
+            linker,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Calls a generated `add_to_linker` with a getter that projects the runtime view onto the
+    /// nested view.
+    pub fn link<F>(mut self, add_to_linker: F) -> Self
+    where
+        F: FnOnce(
+            &mut Linker<RuntimeView<V>>,
+            fn(&mut RuntimeView<V>) -> &mut V,
+        ) -> anyhow::Result<()>,
+    {
+        if let Err(err) = add_to_linker(self.linker, |view| &mut view.nested_view) {
+            self.errors.push(err);
+        }
+        self
+    }
+
+    /// Fails if any interface failed to link, with every failure in the message.
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        match self.errors.len() {
+            0 => Ok(()),
+            1 => Err(self.errors.remove(0)),
+            n => {
+                let errors: Vec<_> = self.errors.iter().map(|err| format!("{err:#}")).collect();
+                Err(anyhow!(
+                    "failed to link {n} interfaces: {}",
+                    errors.join("; ")
+                ))
+            }
+        }
+    }
+}
+
+/// Declares a struct with one field per host interface and implements [`NestedView`] for it by
+/// registering every field's [`HostInterface`] in order.
+///
+/// ```ignore
+/// its_wasmtime::nested_view! {
+///     pub struct MyView {
+///         pub http: HttpHost,
+///         pub storage: StorageHost,
+///     }
+/// }
+///
+/// let runtime = its_wasmtime::runtime(true, MyView { http, storage })?;
+/// ```
+#[macro_export]
+macro_rules! nested_view {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        impl $crate::NestedView for $name {
+            fn add_all_to_linker(
+                &mut self,
+                linker: &mut $crate::wasmtime::component::Linker<$crate::RuntimeView<Self>>,
+            ) -> $crate::anyhow::Result<()> {
+                $(
+                    $crate::HostInterface::<Self>::add_to_linker(
+                        &mut self.$field,
+                        linker,
+                        |view| &mut view.nested_view.$field,
+                    )?;
+                )*
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Implements [`NestedView`] for a tuple whose members are each a [`HostInterface`] of the tuple,
+/// so unrelated host interfaces can be combined without declaring a struct. Member `N` reaches its
+/// own state through `view.nested_view.N`.
+macro_rules! tuple_nested_view {
+    ($($member:ident: $index:tt),+) => {
+        impl<$($member),+> NestedView for ($($member,)+)
+        where
+            $($member: HostInterface<($($member,)+)>),+
+        {
+            fn add_all_to_linker(
+                &mut self,
+                linker: &mut Linker<RuntimeView<Self>>,
+            ) -> anyhow::Result<()> {
+                $(
+                    self.$index
+                        .add_to_linker(linker, |view| &mut view.nested_view.$index)?;
+                )+
+                Ok(())
+            }
+        }
+    };
+}
+
+tuple_nested_view!(A: 0, B: 1);
+tuple_nested_view!(A: 0, B: 1, C: 2);
+
+#[cfg(test)]
+mod nested_view_macro_test {
+    use wasmtime::component::{Linker, Val};
+
+    use crate::{runtime, HostInterface, NestedView, RuntimeView};
+
+    const USES_BOTH: &str = r#"
+        (component
+            (import "its:test/value" (instance $v
+                (export "value" (func (result u32)))))
+            (import "its:test/scale" (instance $s
+                (export "scale" (func (param "x" u32) (result u32)))))
+            (core func $value (canon lower (func $v "value")))
+            (core func $scale (canon lower (func $s "scale")))
+            (core module $m
+                (import "host" "value" (func $value (result i32)))
+                (import "host" "scale" (func $scale (param i32) (result i32)))
+                (func (export "run") (result i32) (call $scale (call $value))))
+            (core instance $host
+                (export "value" (func $value))
+                (export "scale" (func $scale)))
+            (core instance $i (instantiate $m (with "host" (instance $host))))
+            (func (export "run") (result u32) (canon lift (core func $i "run")))
+        )
+    "#;
+
+    struct ValueHost {
+        value: u32,
+    }
+
+    impl<V: NestedView> HostInterface<V> for ValueHost {
+        fn add_to_linker(
+            &mut self,
+            linker: &mut Linker<RuntimeView<V>>,
+            get: fn(&mut RuntimeView<V>) -> &mut Self,
+        ) -> anyhow::Result<()> {
+            linker
+                .instance("its:test/value")?
+                .func_wrap("value", move |mut store, ()| {
+                    Ok((get(store.data_mut()).value,))
+                })?;
+            Ok(())
+        }
+    }
+
+    struct ScaleHost {
+        factor: u32,
+    }
+
+    impl<V: NestedView> HostInterface<V> for ScaleHost {
+        fn add_to_linker(
+            &mut self,
+            linker: &mut Linker<RuntimeView<V>>,
+            get: fn(&mut RuntimeView<V>) -> &mut Self,
+        ) -> anyhow::Result<()> {
+            linker
+                .instance("its:test/scale")?
+                .func_wrap("scale", move |mut store, (x,): (u32,)| {
+                    Ok((x * get(store.data_mut()).factor,))
+                })?;
+            Ok(())
+        }
+    }
+
+    crate::nested_view! {
+        struct ComposedView {
+            value: ValueHost,
+            scale: ScaleHost,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_registers_every_interface() {
+        let mut runtime = runtime(
+            false,
+            ComposedView {
+                value: ValueHost { value: 21 },
+                scale: ScaleHost { factor: 2 },
+            },
+        )
+        .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(USES_BOTH)
+            .expect("failed to compile component");
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let run = instance
+            .get_typed_func::<(), (u32,)>(&mut runtime.store, "run")
+            .expect("missing run export");
+
+        let (result,) = run
+            .call_async(&mut runtime.store, ())
+            .await
+            .expect("failed to invoke run");
+        run.post_return_async(&mut runtime.store)
+            .await
+            .expect("failed to post return");
+        assert_eq!(result, 42);
+
+        runtime.store.data_mut().nested_view.scale.factor = 3;
+        let (result,) = run
+            .call_async(&mut runtime.store, ())
+            .await
+            .expect("failed to invoke run");
+        run.post_return_async(&mut runtime.store)
+            .await
+            .expect("failed to post return");
+        assert_eq!(result, 63);
+    }
+
+    #[tokio::test]
+    async fn it_registers_tuple_members() {
+        let mut runtime = runtime(false, (ValueHost { value: 21 }, ScaleHost { factor: 2 }))
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(USES_BOTH)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        let results = runtime
+            .call_named("plugin", "run", &[])
+            .await
+            .expect("failed to invoke run");
+        assert_eq!(results, [Val::U32(42)]);
+
+        runtime.nested_view_mut().1.factor = 3;
+        let results = runtime
+            .call_named("plugin", "run", &[])
+            .await
+            .expect("failed to invoke run");
+        assert_eq!(results, [Val::U32(63)]);
+    }
+}
@@ -0,0 +1,138 @@
+use std::{collections::HashMap, sync::Arc};
+
+use wasmtime::component::{ComponentType, Lift, Linker, Lower};
+
+/// Interface guests import to read configuration, served by
+/// [`crate::RuntimeBuilder::with_config_store`].
+pub const CONFIG_STORE_INTERFACE: &str = "wasi:config/store@0.2.0-draft";
+
+/// The interface's `error` variant. The host never fails a lookup, but the type is part of the
+/// functions' signatures.
+#[derive(ComponentType, Lift, Lower)]
+#[component(variant)]
+enum StoreError {
+    #[component(name = "upstream")]
+    Upstream(String),
+    #[component(name = "io")]
+    Io(String),
+}
+
+pub(crate) fn add_config_store_to_linker<T>(
+    linker: &mut Linker<T>,
+    values: HashMap<String, String>,
+) -> anyhow::Result<()> {
+    let values = Arc::new(values);
+    let mut instance = linker.instance(CONFIG_STORE_INTERFACE)?;
+
+    instance.func_wrap("get", {
+        let values = values.clone();
+        move |_, (key,): (String,)| -> anyhow::Result<(Result<Option<String>, StoreError>,)> {
+            Ok((Ok(values.get(&key).cloned()),))
+        }
+    })?;
+    instance.func_wrap(
+        "get-all",
+        move |_, (): ()| -> anyhow::Result<(Result<Vec<(String, String)>, StoreError>,)> {
+            let mut all: Vec<_> = values
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            all.sort();
+            Ok((Ok(all),))
+        },
+    )
+}
+
+#[cfg(test)]
+mod config_store_test {
+    use std::collections::HashMap;
+
+    use crate::{test_support::EmptyView, RuntimeBuilder};
+
+    // `value-len` returns the length of the value stored under `greeting`, `-1` if there is
+    // none; `has-missing` returns whether a value is stored under `missing`.
+    const READS_CONFIG: &str = r#"
+        (component
+            (import "wasi:config/store@0.2.0-draft" (instance $store
+                (type $error-def (variant (case "upstream" string) (case "io" string)))
+                (export "error" (type $error (eq $error-def)))
+                (export "get" (func (param "key" string)
+                    (result (result (option string) (error $error)))))))
+            (core module $memory
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 1024))
+                (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $next))
+                    (global.set $next (i32.add (global.get $next) (i32.const 256)))
+                    (local.get $ptr))
+                (data (i32.const 0) "greetingmissing"))
+            (core instance $memory (instantiate $memory))
+            (alias core export $memory "memory" (core memory $mem))
+            (alias core export $memory "realloc" (core func $realloc))
+            (core func $get (canon lower (func $store "get") (memory $mem) (realloc $realloc)))
+            (core module $m
+                (import "host" "memory" (memory 1))
+                (import "host" "get" (func $get (param i32 i32 i32)))
+                (func (export "value-len") (result i32)
+                    (call $get (i32.const 0) (i32.const 8) (i32.const 64))
+                    (if (result i32)
+                        (i32.and
+                            (i32.eqz (i32.load8_u (i32.const 64)))
+                            (i32.eq (i32.load8_u (i32.const 68)) (i32.const 1)))
+                        (then (i32.load (i32.const 76)))
+                        (else (i32.const -1))))
+                (func (export "has-missing") (result i32)
+                    (call $get (i32.const 8) (i32.const 7) (i32.const 96))
+                    (i32.load8_u (i32.const 100))))
+            (core instance $i (instantiate $m
+                (with "host" (instance
+                    (export "memory" (memory $mem))
+                    (export "get" (func $get))))))
+            (func (export "value-len") (result s32) (canon lift (core func $i "value-len")))
+            (func (export "has-missing") (result bool) (canon lift (core func $i "has-missing")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_serves_configured_values() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_config_store(HashMap::from([(
+                "greeting".to_string(),
+                "hello".to_string(),
+            )]))
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(READS_CONFIG)
+            .expect("failed to compile component");
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+
+        let value_len = instance
+            .get_typed_func::<(), (i32,)>(&mut runtime.store, "value-len")
+            .expect("missing value-len export");
+        let (len,) = value_len
+            .call_async(&mut runtime.store, ())
+            .await
+            .expect("failed to invoke value-len");
+        value_len
+            .post_return_async(&mut runtime.store)
+            .await
+            .expect("failed to post return");
+        assert_eq!(len, "hello".len() as i32);
+
+        let has_missing = instance
+            .get_typed_func::<(), (bool,)>(&mut runtime.store, "has-missing")
+            .expect("missing has-missing export");
+        let (found,) = has_missing
+            .call_async(&mut runtime.store, ())
+            .await
+            .expect("failed to invoke has-missing");
+        assert!(!found);
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use tokio_util::sync::CancellationToken;
+use wasmtime::{Engine, UpdateDeadline};
+
+use crate::RuntimeError;
+
+/// What a [`Deadline`] bounds, which decides the error reported when it passes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum DeadlineKind {
+    Instantiation(Duration),
+    Call(Duration),
+}
+
+impl DeadlineKind {
+    fn error(self) -> RuntimeError {
+        match self {
+            DeadlineKind::Instantiation(max_instantiation_time) => {
+                RuntimeError::InstantiationTimedOut {
+                    max_instantiation_time,
+                }
+            }
+            DeadlineKind::Call(timeout) => RuntimeError::Timeout { timeout },
+        }
+    }
+}
+
+#[derive(Default)]
+struct DeadlineState {
+    until: Option<(Instant, DeadlineKind)>,
+    cancellation: Option<CancellationToken>,
+}
+
+/// When the instantiation or call in progress has to finish, if it is bounded, and the token that
+/// cancels it early, if any.
+#[derive(Clone, Default)]
+pub(crate) struct Deadline(Arc<Mutex<DeadlineState>>);
+
+impl Deadline {
+    /// Bounds the operation about to start by `limit`, if there is one, until the returned guard
+    /// is dropped. Holding a guard rather than clearing after the `.await` keeps a dropped future
+    /// from leaving its deadline behind for the next operation.
+    pub(crate) fn start(
+        &self,
+        limit: Option<Duration>,
+        kind: fn(Duration) -> DeadlineKind,
+    ) -> DeadlineGuard {
+        self.0.lock().unwrap().until = limit.map(|limit| (Instant::now() + limit, kind(limit)));
+        DeadlineGuard(self.clone())
+    }
+
+    pub(crate) fn clear(&self) {
+        self.0.lock().unwrap().until = None;
+    }
+
+    /// Fails the operation in progress as soon as `token` is cancelled, until the returned guard
+    /// is dropped.
+    pub(crate) fn watch(&self, token: CancellationToken) -> CancellationGuard {
+        self.0.lock().unwrap().cancellation = Some(token);
+        CancellationGuard(self.clone())
+    }
+
+    fn expired(&self) -> Option<RuntimeError> {
+        let state = self.0.lock().unwrap();
+        if state
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Some(RuntimeError::Cancelled);
+        }
+
+        match state.until {
+            Some((deadline, kind)) if Instant::now() >= deadline => Some(kind.error()),
+            _ => None,
+        }
+    }
+}
+
+/// Clears the deadline set by [`Deadline::start`] when dropped.
+#[must_use]
+pub(crate) struct DeadlineGuard(Deadline);
+
+impl Drop for DeadlineGuard {
+    fn drop(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Stops watching the token passed to [`Deadline::watch`] when dropped.
+#[must_use]
+pub(crate) struct CancellationGuard(Deadline);
+
+impl Drop for CancellationGuard {
+    fn drop(&mut self) {
+        self.0 .0.lock().unwrap().cancellation = None;
+    }
+}
+
+/// Builds the body of the store's epoch deadline callback.
+///
+/// Every tick fails an instantiation or call that is past its deadline or was cancelled, then
+/// either yields to the executor (when a poll budget is configured) or lets the guest continue.
+pub(crate) fn deadline_callback(
+    yield_on_tick: bool,
+    deadline: Deadline,
+) -> impl Fn() -> anyhow::Result<UpdateDeadline> + Send + Sync + 'static {
+    move || {
+        if let Some(error) = deadline.expired() {
+            return Err(error.into());
+        }
+
+        Ok(match yield_on_tick {
+            true => UpdateDeadline::Yield(1),
+            false => UpdateDeadline::Continue(1),
+        })
+    }
+}
+
+/// Background thread that advances an engine's epoch on a fixed interval.
+///
+/// The thread is stopped and joined when the ticker is dropped.
+pub(crate) struct EpochTicker {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EpochTicker {
+    pub(crate) fn spawn(engine: Engine, interval: Duration) -> anyhow::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = thread::Builder::new()
+            .name("its-wasmtime-epoch".into())
+            .spawn({
+                let stop = stop.clone();
+                move || {
+                    while !stop.load(Ordering::Acquire) {
+                        thread::park_timeout(interval);
+                        engine.increment_epoch();
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod epoch_ticker_test {
+    use std::{
+        sync::{Arc, Weak},
+        time::Duration,
+    };
+
+    use crate::{test_support::EmptyView, RuntimeBuilder};
+
+    #[test]
+    fn it_stops_tickers_with_their_runtimes() {
+        let tickers: Vec<Weak<_>> = (0..64)
+            .map(|_| {
+                let runtime = RuntimeBuilder::new()
+                    .nested_view(EmptyView)
+                    .max_instantiation_time(Duration::from_secs(1))
+                    .build()
+                    .expect("Failed to build runtime");
+                let ticker = runtime
+                    .epoch_ticker
+                    .as_ref()
+                    .expect("instantiation timeouts run the ticker");
+                Arc::downgrade(&ticker.stop)
+            })
+            .collect();
+
+        // The ticker thread holds the stop flag until it exits, so a dropped flag means the
+        // thread was joined.
+        assert!(tickers.iter().all(|stop| stop.upgrade().is_none()));
+    }
+}
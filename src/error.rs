@@ -0,0 +1,351 @@
+use std::{future::Future, time::Duration};
+
+use wasmtime::{Store, Trap, WasmBacktrace};
+use wasmtime_wasi::I32Exit;
+
+use crate::{NestedView, Runtime, RuntimeView};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RuntimeError {
+    #[error("failed to create engine")]
+    EngineCreation(#[source] anyhow::Error),
+    /// The engine configuration given to [`crate::RuntimeBuilder::with_config`] doesn't enable
+    /// async support, which WASI and async host functions need. Start it from
+    /// [`crate::default_config`].
+    #[error(
+        "the engine config doesn't enable async support, which the runtime's host functions need"
+    )]
+    SyncAsyncMismatch,
+    #[error("failed to set up linker")]
+    LinkerSetup(#[source] anyhow::Error),
+    #[error("failed to instantiate component")]
+    Instantiation(#[source] anyhow::Error),
+    #[error("the linker does not provide the imports {}", .imports.join(", "))]
+    MissingImports { imports: Vec<String> },
+    /// The guest trapped. `source` is wasmtime's original error, see
+    /// [`RuntimeError::backtrace`] for the guest frames it carries.
+    #[error("guest trapped: {trap}")]
+    Trap {
+        trap: Trap,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("component rejected by validation policy: {0}")]
+    Rejected(String),
+    /// The guest asked to exit with this code. WASI 0.2's `exit` only reports success or
+    /// failure, which arrive as `0` and `1`.
+    #[error("guest exited with code {0}")]
+    Exit(i32),
+    #[error("operation was cancelled")]
+    Cancelled,
+    #[error("guest overflowed its {max_wasm_stack} byte stack")]
+    StackOverflow { max_wasm_stack: usize },
+    #[error("runtime exceeded its {max_lifetime:?} lifetime")]
+    LifetimeExceeded { max_lifetime: Duration },
+    #[error("instantiation did not finish within {max_instantiation_time:?}")]
+    InstantiationTimedOut { max_instantiation_time: Duration },
+    #[error("host function `{function}` exceeded its rate limit")]
+    RateLimited { function: String },
+    #[error("host function `{function}` did not finish within {timeout:?}")]
+    HostTimedOut { function: String, timeout: Duration },
+    #[error("component is quarantined for another {remaining:?}")]
+    Quarantined { remaining: Duration },
+    #[error("guest ran out of fuel")]
+    OutOfFuel,
+    #[error("guest call did not finish within {timeout:?}")]
+    Timeout { timeout: Duration },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Converts an error returned by a guest call into a [`RuntimeError`], picking out the
+    /// failures callers may want to handle specifically.
+    pub fn classify_error(&self, error: anyhow::Error) -> RuntimeError {
+        RuntimeError::classify(error, self.max_wasm_stack)
+    }
+
+    /// Runs a guest call, typically a bindgen `call_*` method, and converts its failure with
+    /// [`Runtime::classify_error`].
+    ///
+    /// `call` gets the store to call into:
+    ///
+    /// ```ignore
+    /// let data = runtime.call_with(|store| example.call_get_data(store)).await?;
+    /// ```
+    pub async fn call_with<'a, R, F, Fut>(&'a mut self, call: F) -> Result<R, RuntimeError>
+    where
+        F: FnOnce(&'a mut Store<RuntimeView<T>>) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>>,
+    {
+        let max_wasm_stack = self.max_wasm_stack;
+        call(&mut self.store)
+            .await
+            .map_err(|error| RuntimeError::classify(error, max_wasm_stack))
+    }
+}
+
+impl RuntimeError {
+    /// Wraps an instantiation failure, keeping errors that are already classified and traps
+    /// raised by the component's start functions.
+    pub(crate) fn instantiation(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<RuntimeError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+
+        match error.downcast_ref::<Trap>().copied() {
+            Some(trap) => RuntimeError::trapped(trap, error),
+            None => RuntimeError::Instantiation(error),
+        }
+    }
+
+    pub(crate) fn classify(error: anyhow::Error, max_wasm_stack: usize) -> Self {
+        let error = match error.downcast::<RuntimeError>() {
+            Ok(error) => return error,
+            Err(error) => error,
+        };
+        if let Some(exit) = error.downcast_ref::<I32Exit>() {
+            return RuntimeError::Exit(exit.0);
+        }
+
+        match error.downcast_ref::<Trap>().copied() {
+            Some(Trap::StackOverflow) => RuntimeError::StackOverflow { max_wasm_stack },
+            Some(Trap::OutOfFuel) => RuntimeError::OutOfFuel,
+            Some(trap) => RuntimeError::trapped(trap, error),
+            None => RuntimeError::Other(error),
+        }
+    }
+
+    fn trapped(trap: Trap, error: anyhow::Error) -> Self {
+        RuntimeError::Trap {
+            trap,
+            source: error,
+        }
+    }
+
+    /// The guest frames of a [`RuntimeError::Trap`] when wasm backtraces are enabled, with names
+    /// and source locations if debug info is too.
+    pub fn backtrace(&self) -> Option<&WasmBacktrace> {
+        match self {
+            RuntimeError::Trap { source, .. } => source.downcast_ref::<WasmBacktrace>(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod stack_overflow_test {
+    use crate::{test_support::EmptyView, RuntimeBuilder, RuntimeError};
+
+    const RECURSE: &str = r#"
+        (component
+            (core module $m
+                (func $recurse (export "recurse") (param i32) (result i32)
+                    (if (result i32) (i32.eqz (local.get 0))
+                        (then (i32.const 0))
+                        (else (i32.add
+                            (i32.const 1)
+                            (call $recurse (i32.sub (local.get 0) (i32.const 1))))))))
+            (core instance $i (instantiate $m))
+            (func (export "recurse") (param "depth" u32) (result u32)
+                (canon lift (core func $i "recurse")))
+        )
+    "#;
+
+    async fn recurse(max_wasm_stack: usize, depth: u32) -> Result<u32, RuntimeError> {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .max_wasm_stack(max_wasm_stack)
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(RECURSE)
+            .expect("failed to compile component");
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let func = instance
+            .get_typed_func::<(u32,), (u32,)>(&mut runtime.store, "recurse")
+            .expect("missing recurse export");
+
+        match func.call_async(&mut runtime.store, (depth,)).await {
+            Ok((result,)) => {
+                func.post_return_async(&mut runtime.store)
+                    .await
+                    .expect("failed to post return");
+                Ok(result)
+            }
+            Err(err) => Err(runtime.classify_error(err)),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reports_stack_overflow() {
+        match recurse(64 * 1024, 50_000).await {
+            Err(RuntimeError::StackOverflow { max_wasm_stack }) => {
+                assert_eq!(max_wasm_stack, 64 * 1024)
+            }
+            other => panic!("expected a stack overflow, got {other:?}"),
+        }
+
+        assert_eq!(recurse(8 * 1024 * 1024, 50_000).await.unwrap(), 50_000);
+    }
+}
+
+#[cfg(test)]
+mod trap_test {
+    use wasmtime::Trap;
+
+    use crate::{test_support::EmptyView, RuntimeBuilder, RuntimeError};
+
+    const UNREACHABLE: &str = r#"
+        (component
+            (core module $m
+                (func (export "boom") unreachable))
+            (core instance $i (instantiate $m))
+            (func (export "boom") (canon lift (core func $i "boom")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_reports_other_traps() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_backtraces(true)
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(UNREACHABLE)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        let err = runtime
+            .call_named("plugin", "boom", &[])
+            .await
+            .expect_err("unreachable should trap");
+        match &err {
+            RuntimeError::Trap { trap, .. } => {
+                assert_eq!(*trap, Trap::UnreachableCodeReached);
+                let backtrace = err.backtrace().expect("backtraces are enabled");
+                assert!(!backtrace.frames().is_empty());
+            }
+            other => panic!("expected a trap, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_classifies_calls() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(UNREACHABLE)
+            .expect("failed to compile component");
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let boom = instance
+            .get_typed_func::<(), ()>(&mut runtime.store, "boom")
+            .expect("missing boom export");
+
+        let err = runtime
+            .call_with(|store| boom.call_async(store, ()))
+            .await
+            .expect_err("unreachable should trap");
+        assert!(matches!(
+            err,
+            RuntimeError::Trap {
+                trap: Trap::UnreachableCodeReached,
+                ..
+            }
+        ));
+        assert!(err.to_string().contains("unreachable"));
+
+        let causes: Vec<String> = anyhow::Error::from(err)
+            .chain()
+            .skip(1)
+            .map(ToString::to_string)
+            .collect();
+        assert!(
+            causes.iter().any(|cause| cause.contains("unreachable")),
+            "the original trap should stay in the chain: {causes:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod exit_test {
+    use wasmtime_wasi::I32Exit;
+
+    use crate::{test_support::EmptyView, RuntimeBuilder, RuntimeError};
+
+    const EXITS: &str = r#"
+        (component
+            (import "its:test/process" (instance $process
+                (export "exit" (func (param "code" s32)))))
+            (import "wasi:cli/exit@0.2.0" (instance $cli
+                (export "exit" (func (param "status" (result))))))
+            (core func $exit (canon lower (func $process "exit")))
+            (core func $wasi-exit (canon lower (func $cli "exit")))
+            (core module $m
+                (import "host" "exit" (func $exit (param i32)))
+                (import "host" "wasi-exit" (func $wasi-exit (param i32)))
+                (func (export "exit-42") (call $exit (i32.const 42)))
+                (func (export "fail") (call $wasi-exit (i32.const 1))))
+            (core instance $i (instantiate $m
+                (with "host" (instance
+                    (export "exit" (func $exit))
+                    (export "wasi-exit" (func $wasi-exit))))))
+            (func (export "exit-42") (canon lift (core func $i "exit-42")))
+            (func (export "fail") (canon lift (core func $i "fail")))
+        )
+    "#;
+
+    async fn exit_code(export: &str) -> RuntimeError {
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .configure_linker(|linker| {
+                linker.instance("its:test/process")?.func_wrap_async(
+                    "exit",
+                    |_, (code,): (i32,)| {
+                        Box::new(async move { Err::<(), _>(I32Exit(code).into()) })
+                    },
+                )
+            })
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(EXITS)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        runtime
+            .call_named("plugin", export, &[])
+            .await
+            .expect_err("the guest should exit")
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_exit_code() {
+        assert!(matches!(exit_code("exit-42").await, RuntimeError::Exit(42)));
+        assert!(matches!(exit_code("fail").await, RuntimeError::Exit(1)));
+    }
+}
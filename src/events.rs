@@ -0,0 +1,126 @@
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use crate::{NestedView, Runtime};
+
+/// Events buffered per subscriber before the oldest are dropped.
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeEvent {
+    Compiled,
+    Instantiated,
+    CallStarted { export: String },
+    CallFinished { export: String },
+    Trapped { export: String, message: String },
+    Dropped,
+}
+
+pub(crate) struct EventPublisher {
+    sender: broadcast::Sender<RuntimeEvent>,
+}
+
+impl EventPublisher {
+    pub(crate) fn new() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Never blocks: without subscribers the event is discarded, and subscribers that fall
+    /// behind lose their oldest events.
+    pub(crate) fn publish(&self, event: RuntimeEvent) {
+        let _ = self.sender.send(event);
+    }
+}
+
+impl Drop for EventPublisher {
+    fn drop(&mut self) {
+        self.publish(RuntimeEvent::Dropped);
+    }
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Subscribes to lifecycle events published from now on.
+    ///
+    /// The stream ends after [`RuntimeEvent::Dropped`], when the runtime is dropped. Subscribers
+    /// never slow the runtime down: one that falls more than a few hundred events behind skips
+    /// the ones it missed.
+    pub fn events(&self) -> impl Stream<Item = RuntimeEvent> {
+        BroadcastStream::new(self.events.sender.subscribe()).filter_map(Result::ok)
+    }
+}
+
+#[cfg(test)]
+mod events_test {
+    use tokio_stream::StreamExt;
+
+    use crate::{runtime, test_support::EmptyView, RuntimeEvent};
+
+    const ANSWER: &str = r#"
+        (component
+            (core module $m
+                (func (export "f") (result i32) i32.const 42)
+                (func (export "trap") (result i32) unreachable))
+            (core instance $i (instantiate $m))
+            (func (export "answer") (result u32) (canon lift (core func $i "f")))
+            (func (export "trap") (result u32) (canon lift (core func $i "trap")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_publishes_lifecycle_events() {
+        let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+        let events = runtime.events();
+
+        let component = runtime
+            .load_component(ANSWER)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+        runtime
+            .call_named("plugin", "answer", &[])
+            .await
+            .expect("failed to invoke answer");
+        assert!(runtime.call_named("plugin", "trap", &[]).await.is_err());
+        drop(runtime);
+
+        let events: Vec<_> = events
+            .map(|event| match event {
+                RuntimeEvent::Trapped { export, .. } => RuntimeEvent::Trapped {
+                    export,
+                    message: String::new(),
+                },
+                event => event,
+            })
+            .collect()
+            .await;
+
+        assert_eq!(
+            events,
+            vec![
+                RuntimeEvent::Compiled,
+                RuntimeEvent::Instantiated,
+                RuntimeEvent::CallStarted {
+                    export: "answer".into()
+                },
+                RuntimeEvent::CallFinished {
+                    export: "answer".into()
+                },
+                RuntimeEvent::CallStarted {
+                    export: "trap".into()
+                },
+                RuntimeEvent::Trapped {
+                    export: "trap".into(),
+                    message: String::new(),
+                },
+                RuntimeEvent::Dropped,
+            ]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use wasmparser::{Parser, Payload};
+use wasmtime::component::Component;
+
+use crate::{load, NestedView, Runtime};
+
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// Bytes per table element in Wasmtime's table representation.
+const TABLE_ELEMENT_SIZE: u64 = 8;
+
+/// Conservative estimate of the memory instantiating a component takes, see
+/// [`Runtime::estimate_footprint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FootprintEstimate {
+    /// Initial size of every linear memory the component defines.
+    pub linear_memory: u64,
+    /// Initial size of every table the component defines.
+    pub tables: u64,
+    /// Size of the compiled code image in memory.
+    pub compiled_code: u64,
+}
+
+impl FootprintEstimate {
+    pub fn total(&self) -> u64 {
+        self.linear_memory + self.tables + self.compiled_code
+    }
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Estimates how much memory instantiating the component in `bytes` takes.
+    ///
+    /// The estimate uses initial rather than maximum sizes, and counts each core module's
+    /// memories and tables once, as most components instantiate every module once. Memory
+    /// grown at runtime and host-side state are not included. The component is compiled, on the
+    /// compilation pool if one is configured, to measure its code, so this costs as much as
+    /// loading it.
+    pub fn estimate_footprint(&self, bytes: &[u8]) -> anyhow::Result<FootprintEstimate> {
+        let component = load::compile(&self.engine, self.compilation_pool.as_deref(), None, bytes)?;
+        measure(bytes, &component)
+    }
+}
+
+/// Estimates the footprint of `component`, compiled from `bytes`, without compiling it again.
+pub(crate) fn measure(bytes: &[u8], component: &Component) -> anyhow::Result<FootprintEstimate> {
+    let (linear_memory, tables) = declared_sizes(bytes)?;
+    let image = component.image_range();
+
+    Ok(FootprintEstimate {
+        linear_memory,
+        tables,
+        compiled_code: (image.end as usize - image.start as usize) as u64,
+    })
+}
+
+/// Sums the initial sizes of the memories and tables declared in `bytes`, which may be in
+/// text format.
+fn declared_sizes(bytes: &[u8]) -> anyhow::Result<(u64, u64)> {
+    let bytes = wat::parse_bytes(bytes)?;
+    let mut linear_memory = 0;
+    let mut tables = 0;
+
+    for payload in Parser::new(0).parse_all(&bytes) {
+        match payload? {
+            Payload::MemorySection(reader) => {
+                for memory in reader {
+                    linear_memory += u64::from(memory?.initial) * WASM_PAGE_SIZE;
+                }
+            }
+            Payload::TableSection(reader) => {
+                for table in reader {
+                    tables += u64::from(table?.ty.initial) * TABLE_ELEMENT_SIZE;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok((linear_memory, tables))
+}
+
+#[cfg(test)]
+mod footprint_test {
+    use crate::{runtime, test_support::EmptyView};
+
+    const TWO_MEMORIES: &str = r#"
+        (component
+            (core module $a
+                (memory 2)
+                (table 10 funcref))
+            (core module $b
+                (memory 1 5))
+            (core instance (instantiate $a))
+            (core instance (instantiate $b))
+        )
+    "#;
+
+    #[test]
+    fn it_estimates_declared_sizes() {
+        let runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+        let bytes = wat::parse_str(TWO_MEMORIES).expect("invalid wat");
+
+        let estimate = runtime
+            .estimate_footprint(&bytes)
+            .expect("failed to estimate footprint");
+
+        assert_eq!(estimate.linear_memory, 3 * 64 * 1024);
+        assert_eq!(estimate.tables, 10 * 8);
+        assert!(estimate.compiled_code > 0);
+        assert_eq!(
+            estimate.total(),
+            estimate.linear_memory + estimate.tables + estimate.compiled_code
+        );
+    }
+}
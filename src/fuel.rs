@@ -0,0 +1,153 @@
+use anyhow::anyhow;
+
+use crate::{NestedView, Runtime};
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Adds `fuel` to the store's remaining fuel, e.g. to retry a call that ran out.
+    ///
+    /// Fails if the runtime wasn't built with [`crate::RuntimeBuilder::with_fuel`].
+    pub fn add_fuel(&mut self, fuel: u64) -> anyhow::Result<()> {
+        let Some(added) = &mut self.fuel_added else {
+            return Err(anyhow!("fuel metering is not enabled for this runtime"));
+        };
+
+        let remaining = self.store.get_fuel()?;
+        self.store.set_fuel(remaining.saturating_add(fuel))?;
+        *added = added.saturating_add(fuel);
+        Ok(())
+    }
+
+    /// Restores the store's fuel to the amount the runtime was built with and re-arms its epoch
+    /// deadline, so a store reused across requests starts every call with the same budget.
+    ///
+    /// This also lets a store that ran out of fuel run again, though an instance that trapped
+    /// can't be called again and has to be re-instantiated. [`Runtime::fuel_consumed`] counts
+    /// from the reset.
+    pub fn reset_limits(&mut self) -> anyhow::Result<()> {
+        if let Some(fuel) = self.initial_fuel {
+            self.store.set_fuel(fuel)?;
+            self.fuel_added = Some(fuel);
+        }
+        if self.epoch_ticker.is_some() {
+            self.deadline.clear();
+            self.store.set_epoch_deadline(1);
+        }
+        Ok(())
+    }
+
+    /// Fuel consumed so far by instantiations and calls, or `None` without fuel metering.
+    pub fn fuel_consumed(&self) -> Option<u64> {
+        let added = self.fuel_added?;
+        let remaining = self.store.get_fuel().ok()?;
+        Some(added.saturating_sub(remaining))
+    }
+}
+
+#[cfg(test)]
+mod fuel_test {
+    use crate::{
+        test_support::{EmptyView, SPIN},
+        RuntimeBuilder, RuntimeError,
+    };
+
+    #[tokio::test]
+    async fn it_bounds_execution_with_fuel() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_fuel(10_000)
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(SPIN)
+            .expect("failed to compile component");
+
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let spin = instance
+            .get_typed_func::<(u32,), (u32,)>(&mut runtime.store, "spin")
+            .expect("missing spin export");
+        let err = spin
+            .call_async(&mut runtime.store, (1_000_000,))
+            .await
+            .expect_err("spin should run out of fuel");
+        assert!(matches!(
+            runtime.classify_error(err),
+            RuntimeError::OutOfFuel
+        ));
+        assert_eq!(runtime.fuel_consumed(), Some(10_000));
+
+        runtime.add_fuel(100_000_000).unwrap();
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let spin = instance
+            .get_typed_func::<(u32,), (u32,)>(&mut runtime.store, "spin")
+            .expect("missing spin export");
+        let (result,) = spin
+            .call_async(&mut runtime.store, (1_000_000,))
+            .await
+            .expect("spin should finish with more fuel");
+        assert_eq!(result, 1_000_000);
+        assert!(runtime.fuel_consumed().unwrap() > 10_000);
+    }
+
+    #[tokio::test]
+    async fn it_resets_fuel_between_calls() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_fuel(10_000)
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(SPIN)
+            .expect("failed to compile component");
+
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let spin = instance
+            .get_typed_func::<(u32,), (u32,)>(&mut runtime.store, "spin")
+            .expect("missing spin export");
+        spin.call_async(&mut runtime.store, (1_000_000,))
+            .await
+            .expect_err("spin should run out of fuel");
+        assert_eq!(runtime.store.get_fuel().unwrap(), 0);
+
+        runtime.reset_limits().unwrap();
+        assert_eq!(runtime.store.get_fuel().unwrap(), 10_000);
+        assert_eq!(runtime.fuel_consumed(), Some(0));
+
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let spin = instance
+            .get_typed_func::<(u32,), (u32,)>(&mut runtime.store, "spin")
+            .expect("missing spin export");
+        let (result,) = spin
+            .call_async(&mut runtime.store, (100,))
+            .await
+            .expect("spin should finish after the reset");
+        assert_eq!(result, 100);
+    }
+
+    #[test]
+    fn it_reports_disabled_fuel() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .build()
+            .expect("Failed to build runtime");
+
+        assert_eq!(runtime.fuel_consumed(), None);
+        assert!(runtime.add_fuel(1).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::{Arc, RwLock};
+
+use wasmtime::component::{Linker, Val};
+
+use crate::rate_limit::RateLimits;
+
+pub(crate) type DynHostFn = Arc<dyn Fn(&[Val], &mut [Val]) -> anyhow::Result<()> + Send + Sync>;
+
+struct GlobalHostFnEntry {
+    module: String,
+    name: String,
+    func: DynHostFn,
+}
+
+static GLOBAL_HOST_FNS: RwLock<Vec<GlobalHostFnEntry>> = RwLock::new(Vec::new());
+
+/// Registers a host function that is added to every runtime built afterwards.
+///
+/// Functions are defined on the linker instance named `module` (e.g. `host:log/sink`) after WASI
+/// and before the nested view, so `module` must not be an interface either of those also
+/// defines. Registering the same `module` and `name` again replaces the earlier function.
+/// Runtimes that were already built are unaffected. The registry may be used from any thread.
+///
+/// The registry is process-wide: a registered function reaches every runtime built in the
+/// process from then on, including those built by other libraries that use this crate, until
+/// it is removed with [`unregister_global_host_fn`].
+pub fn register_global_host_fn<F>(module: impl Into<String>, name: impl Into<String>, func: F)
+where
+    F: Fn(&[Val], &mut [Val]) -> anyhow::Result<()> + Send + Sync + 'static,
+{
+    let entry = GlobalHostFnEntry {
+        module: module.into(),
+        name: name.into(),
+        func: Arc::new(func),
+    };
+
+    let mut registry = GLOBAL_HOST_FNS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    registry.retain(|e| e.module != entry.module || e.name != entry.name);
+    registry.push(entry);
+}
+
+/// Removes the host function registered as `module` and `name`, returning whether there was one.
+///
+/// Runtimes built afterwards no longer get the function; runtimes that were already built keep
+/// it.
+pub fn unregister_global_host_fn(module: &str, name: &str) -> bool {
+    let mut registry = GLOBAL_HOST_FNS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let len = registry.len();
+    registry.retain(|e| e.module != module || e.name != name);
+    registry.len() != len
+}
+
+/// Defines every registered function on `linker` and returns their paths.
+pub(crate) fn add_global_host_fns_to_linker<T>(
+    linker: &mut Linker<T>,
+    rate_limits: &RateLimits,
+) -> anyhow::Result<Vec<String>> {
+    let registry = GLOBAL_HOST_FNS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let mut linked = Vec::new();
+    let mut modules: Vec<&str> = registry.iter().map(|e| e.module.as_str()).collect();
+    modules.sort_unstable();
+    modules.dedup();
+
+    for module in modules {
+        let mut instance = linker.instance(module)?;
+        for entry in registry.iter().filter(|e| e.module == module) {
+            let path = format!("{module}#{}", entry.name);
+            let func = rate_limits.apply(path.clone(), entry.func.clone());
+            instance.func_new(&entry.name, move |_, params, results| func(params, results))?;
+            linked.push(path);
+        }
+    }
+
+    Ok(linked)
+}
+
+#[cfg(test)]
+mod global_host_fn_test {
+    use wasmtime::component::{Component, Val};
+
+    use crate::{
+        register_global_host_fn, runtime, test_support::EmptyView, unregister_global_host_fn,
+    };
+
+    /// Keeps a global function registered for the duration of a test, even if it panics.
+    struct Registration {
+        module: &'static str,
+        name: &'static str,
+    }
+
+    impl Drop for Registration {
+        fn drop(&mut self) {
+            unregister_global_host_fn(self.module, self.name);
+        }
+    }
+
+    const CALLS_GLOBAL: &str = r#"
+        (component
+            (import "its:test/globals" (instance $g
+                (export "add-one" (func (param "n" u32) (result u32)))))
+            (core func $add-one (canon lower (func $g "add-one")))
+            (core module $m
+                (import "host" "add-one" (func $add-one (param i32) (result i32)))
+                (func (export "run") (result i32) (call $add-one (i32.const 41))))
+            (core instance $host (export "add-one" (func $add-one)))
+            (core instance $i (instantiate $m (with "host" (instance $host))))
+            (func (export "run") (result u32) (canon lift (core func $i "run")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_exposes_global_fns_to_every_runtime() {
+        register_global_host_fn("its:test/globals", "add-one", |params, results| {
+            let Val::U32(n) = params[0] else {
+                anyhow::bail!("expected a u32 parameter");
+            };
+            results[0] = Val::U32(n + 1);
+            Ok(())
+        });
+        let registration = Registration {
+            module: "its:test/globals",
+            name: "add-one",
+        };
+
+        for _ in 0..2 {
+            let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+            let component =
+                Component::new(&runtime.engine, CALLS_GLOBAL).expect("failed to compile");
+            let instance = runtime
+                .linker
+                .instantiate_async(&mut runtime.store, &component)
+                .await
+                .expect("failed to instantiate component");
+            let run = instance
+                .get_typed_func::<(), (u32,)>(&mut runtime.store, "run")
+                .expect("missing run export");
+
+            let (result,) = run
+                .call_async(&mut runtime.store, ())
+                .await
+                .expect("failed to invoke run");
+            run.post_return_async(&mut runtime.store)
+                .await
+                .expect("failed to post return");
+
+            assert_eq!(result, 42);
+        }
+
+        drop(registration);
+        assert!(!unregister_global_host_fn("its:test/globals", "add-one"));
+
+        let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+        let component = Component::new(&runtime.engine, CALLS_GLOBAL).expect("failed to compile");
+        runtime
+            .linker
+            .instantiate_async(&mut runtime.store, &component)
+            .await
+            .expect_err("unregistered function should no longer be linked");
+    }
+}
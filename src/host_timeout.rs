@@ -0,0 +1,88 @@
+use std::{future::Future, time::Duration};
+
+use crate::RuntimeError;
+
+/// Runs the host side of an import, failing it with [`RuntimeError::HostTimedOut`] if it doesn't
+/// finish within `timeout`.
+///
+/// Wrap the body of an async host function in it (a bindgen `Host` method or a `func_wrap_async`
+/// closure) so a host import that hangs aborts the guest call instead of stalling the store. The
+/// error traps the guest and comes back out of the call, where [`crate::Runtime::classify_error`]
+/// recovers it. `function` only names the import in the error.
+pub async fn with_host_timeout<F, R>(
+    function: &str,
+    timeout: Duration,
+    call: F,
+) -> wasmtime::Result<R>
+where
+    F: Future<Output = wasmtime::Result<R>>,
+{
+    match tokio::time::timeout(timeout, call).await {
+        Ok(result) => result,
+        Err(_) => Err(RuntimeError::HostTimedOut {
+            function: function.into(),
+            timeout,
+        }
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod host_timeout_test {
+    use std::time::Duration;
+
+    use crate::{test_support::EmptyView, with_host_timeout, RuntimeBuilder, RuntimeError};
+
+    const CALLS_SLOW: &str = r#"
+        (component
+            (import "its:test/slow" (instance $slow
+                (export "wait" (func))))
+            (core func $wait (canon lower (func $slow "wait")))
+            (core module $m
+                (import "host" "wait" (func $wait))
+                (func (export "run") (call $wait)))
+            (core instance $i (instantiate $m
+                (with "host" (instance (export "wait" (func $wait))))))
+            (func (export "run") (canon lift (core func $i "run")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_fails_slow_host_functions() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .configure_linker(|linker| {
+                linker
+                    .instance("its:test/slow")?
+                    .func_wrap_async("wait", |_, (): ()| {
+                        Box::new(with_host_timeout(
+                            "wait",
+                            Duration::from_millis(50),
+                            async {
+                                tokio::time::sleep(Duration::from_secs(60)).await;
+                                Ok(())
+                            },
+                        ))
+                    })
+            })
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(CALLS_SLOW)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        let err = runtime
+            .call_named("plugin", "run", &[])
+            .await
+            .expect_err("the host function should time out");
+        assert!(matches!(
+            err,
+            RuntimeError::HostTimedOut { function, .. } if function == "wait"
+        ));
+    }
+}
@@ -0,0 +1,570 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use tokio::runtime::Handle;
+use tokio_util::sync::CancellationToken;
+use wasmtime::{
+    component::{Component, Exports, Instance, Val},
+    Trap,
+};
+
+use crate::{
+    epoch::DeadlineKind, trace::traced_async, NestedView, Runtime, RuntimeError, RuntimeEvent,
+};
+
+pub(crate) type ExportFallback =
+    Arc<dyn Fn(&str, &[Val]) -> anyhow::Result<Vec<Val>> + Send + Sync>;
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Instantiates `component` into this runtime's store and returns the raw instance.
+    ///
+    /// This is an escape hatch for when bindgen bindings aren't available or don't cover what
+    /// you need.
+    ///
+    /// Fails with [`RuntimeError::Quarantined`] if a quarantine policy is configured and the
+    /// component failed too many calls recently.
+    pub async fn instantiate_component(
+        &mut self,
+        component: &Component,
+    ) -> Result<Instance, RuntimeError> {
+        self.check_quarantine(component)
+            .map_err(RuntimeError::instantiation)?;
+        self.instantiate_unchecked(component)
+            .await
+            .map_err(RuntimeError::instantiation)
+    }
+
+    async fn instantiate_unchecked(&mut self, component: &Component) -> anyhow::Result<Instance> {
+        let deadline = self
+            .deadline
+            .start(self.max_instantiation_time, DeadlineKind::Instantiation);
+        let instance =
+            traced_async!("instantiate"; self.linker.instantiate_async(&mut self.store, component));
+        drop(deadline);
+
+        let instance = match instance {
+            Ok(instance) => instance,
+            Err(err) => {
+                let imports = self.missing_imports(component);
+                return Err(match imports.is_empty() {
+                    true => err,
+                    false => RuntimeError::MissingImports { imports }.into(),
+                });
+            }
+        };
+        self.events.publish(RuntimeEvent::Instantiated);
+        Ok(instance)
+    }
+
+    /// Looks up exports of `instance`, including those of nested instances.
+    pub fn instance_exports(&mut self, instance: &Instance) -> Exports<'_> {
+        instance.exports(&mut self.store)
+    }
+
+    /// Instantiates `component` and remembers the instance under `name`, so it can be used with
+    /// [`Runtime::call_named`]. Names must be unique within a runtime.
+    pub async fn instantiate_named(
+        &mut self,
+        name: &str,
+        component: &Component,
+    ) -> Result<Instance, RuntimeError> {
+        if self.named_instances.contains_key(name) {
+            return Err(anyhow!("an instance named `{name}` already exists").into());
+        }
+
+        let key = self
+            .check_quarantine(component)
+            .map_err(RuntimeError::instantiation)?;
+        let instance = self
+            .instantiate_unchecked(component)
+            .await
+            .map_err(RuntimeError::instantiation)?;
+        self.named_instances.insert(name.into(), instance);
+        self.track_instance(name, key);
+        Ok(instance)
+    }
+
+    /// Fails with [`RuntimeError::LifetimeExceeded`] once the runtime has outlived the builder's
+    /// `max_lifetime`.
+    pub fn check_lifetime(&self) -> Result<(), RuntimeError> {
+        match self.max_lifetime {
+            Some(max_lifetime) if self.created_at.elapsed() >= max_lifetime => {
+                Err(RuntimeError::LifetimeExceeded { max_lifetime })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn named_instance(&self, name: &str) -> Option<Instance> {
+        self.named_instances.get(name).copied()
+    }
+
+    /// Calls the function `export` of the instance registered as `name` with dynamically typed
+    /// arguments.
+    ///
+    /// If the instance has no such export, the builder's export fallback handles the call instead,
+    /// if one was configured. Failures are classified like [`Runtime::classify_error`] does.
+    pub async fn call_named(
+        &mut self,
+        name: &str,
+        export: &str,
+        args: &[Val],
+    ) -> Result<Vec<Val>, RuntimeError> {
+        self.check_lifetime()?;
+
+        let instance = self
+            .named_instance(name)
+            .ok_or_else(|| anyhow!("no instance named `{name}`"))?;
+        let Some(func) = instance.get_func(&mut self.store, export) else {
+            return match &self.export_fallback {
+                Some(fallback) => fallback(export, args).map_err(|err| self.classify_error(err)),
+                None => Err(anyhow!("instance `{name}` has no export `{export}`").into()),
+            };
+        };
+
+        self.events.publish(RuntimeEvent::CallStarted {
+            export: export.into(),
+        });
+
+        let mut results = vec![Val::Bool(false); func.results(&self.store).len()];
+        let deadline = self.deadline.start(self.call_timeout, DeadlineKind::Call);
+        let outcome = traced_async!("call", instance = name, export = export; async {
+            func.call_async(&mut self.store, args, &mut results).await?;
+            func.post_return_async(&mut self.store).await
+        });
+        drop(deadline);
+
+        match outcome {
+            Ok(()) => {
+                self.events.publish(RuntimeEvent::CallFinished {
+                    export: export.into(),
+                });
+                Ok(results)
+            }
+            Err(err) => {
+                if err.downcast_ref::<Trap>().is_some() {
+                    self.record_trap(name);
+                    self.events.publish(RuntimeEvent::Trapped {
+                        export: export.into(),
+                        message: format!("{err:#}"),
+                    });
+                }
+                Err(self.classify_error(err))
+            }
+        }
+    }
+
+    /// Like [`Runtime::call_named`], but fails with [`RuntimeError::Cancelled`] as soon as `token`
+    /// is cancelled, e.g. when the client that asked for the call disconnects.
+    ///
+    /// The token is checked on every epoch tick, so the runtime must be built with
+    /// [`RuntimeBuilder::cancellable`](crate::RuntimeBuilder::cancellable). Like a timeout, the
+    /// interrupted instance traps and can't be called again.
+    pub async fn call_cancellable(
+        &mut self,
+        name: &str,
+        export: &str,
+        args: &[Val],
+        token: &CancellationToken,
+    ) -> Result<Vec<Val>, RuntimeError> {
+        if token.is_cancelled() {
+            return Err(RuntimeError::Cancelled);
+        }
+
+        let _watching = self.deadline.watch(token.clone());
+        self.call_named(name, export, args).await
+    }
+
+    /// Synchronous version of [`Runtime::call_named`] for code that runs inside a tokio runtime
+    /// but can't `.await`.
+    ///
+    /// The call is driven to completion on the current worker thread through
+    /// [`tokio::task::block_in_place`], without spawning a separate runtime. This must be called
+    /// from a multi-threaded tokio runtime; it panics on a current-thread runtime or outside of
+    /// tokio.
+    pub fn call_blocking(
+        &mut self,
+        name: &str,
+        export: &str,
+        args: &[Val],
+    ) -> Result<Vec<Val>, RuntimeError> {
+        tokio::task::block_in_place(|| {
+            Handle::current().block_on(self.call_named(name, export, args))
+        })
+    }
+}
+
+#[cfg(test)]
+mod raw_instance_test {
+    use crate::{runtime, test_support::EmptyView};
+
+    const NESTED: &str = r#"
+        (component
+            (core module $m (func (export "f") (result i32) i32.const 7))
+            (core instance $i (instantiate $m))
+            (func $answer (result u32) (canon lift (core func $i "f")))
+            (instance $inner (export "answer" (func $answer)))
+            (export "inner" (instance $inner))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_exposes_the_raw_instance() {
+        let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(NESTED)
+            .expect("failed to compile component");
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+
+        let answer = runtime
+            .instance_exports(&instance)
+            .instance("inner")
+            .expect("missing inner instance")
+            .typed_func::<(), (u32,)>("answer")
+            .expect("missing answer export");
+
+        let (result,) = answer
+            .call_async(&mut runtime.store, ())
+            .await
+            .expect("failed to invoke answer");
+        answer
+            .post_return_async(&mut runtime.store)
+            .await
+            .expect("failed to post return");
+
+        assert_eq!(result, 7);
+    }
+}
+
+#[cfg(test)]
+mod named_instance_test {
+    use wasmtime::component::Val;
+
+    use crate::{
+        runtime,
+        test_support::{answer, EmptyView},
+    };
+
+    #[tokio::test]
+    async fn it_calls_instances_by_name() {
+        let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        for (name, value) in [("first", 1), ("second", 2)] {
+            let component = runtime
+                .load_component(answer(value))
+                .expect("failed to compile component");
+            runtime
+                .instantiate_named(name, &component)
+                .await
+                .expect("failed to instantiate component");
+        }
+
+        let first = runtime.call_named("first", "answer", &[]).await.unwrap();
+        let second = runtime.call_named("second", "answer", &[]).await.unwrap();
+        assert_eq!(first, vec![Val::U32(1)]);
+        assert_eq!(second, vec![Val::U32(2)]);
+
+        let component = runtime.load_component(answer(3)).unwrap();
+        assert!(runtime
+            .instantiate_named("first", &component)
+            .await
+            .is_err());
+        assert!(runtime.call_named("third", "answer", &[]).await.is_err());
+    }
+}
+
+#[cfg(test)]
+mod export_fallback_test {
+    use wasmtime::component::Val;
+
+    use crate::{
+        test_support::{answer, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_routes_unknown_exports_to_the_fallback() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .export_fallback(|export, args| {
+                Ok(vec![Val::String(format!("{export}/{}", args.len()))])
+            })
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        let present = runtime.call_named("plugin", "answer", &[]).await.unwrap();
+        assert_eq!(present, vec![Val::U32(42)]);
+
+        let missing = runtime
+            .call_named("plugin", "missing", &[Val::U32(1)])
+            .await
+            .unwrap();
+        assert_eq!(missing, vec![Val::String("missing/1".into())]);
+    }
+}
+
+#[cfg(test)]
+mod call_blocking_test {
+    use wasmtime::component::Val;
+
+    use crate::{
+        runtime,
+        test_support::{answer, EmptyView},
+    };
+
+    fn sync_caller(runtime: &mut crate::Runtime<EmptyView>) -> Vec<Val> {
+        runtime
+            .call_blocking("plugin", "answer", &[])
+            .expect("failed to invoke answer")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn it_calls_guests_from_sync_code() {
+        let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        assert_eq!(sync_caller(&mut runtime), vec![Val::U32(42)]);
+    }
+}
+
+#[cfg(test)]
+mod max_lifetime_test {
+    use std::time::Duration;
+
+    use crate::{
+        test_support::{answer, EmptyView},
+        RuntimeBuilder, RuntimeError,
+    };
+
+    #[tokio::test]
+    async fn it_fails_calls_after_the_lifetime() {
+        let lifetime = Duration::from_millis(200);
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .max_lifetime(lifetime)
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        loop {
+            match runtime.call_named("plugin", "answer", &[]).await {
+                Ok(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+                Err(RuntimeError::LifetimeExceeded { max_lifetime }) => {
+                    assert_eq!(max_lifetime, lifetime);
+                    break;
+                }
+                Err(err) => panic!("expected the lifetime to be exceeded, got {err}"),
+            }
+        }
+
+        assert!(runtime.call_named("plugin", "answer", &[]).await.is_err());
+    }
+}
+
+#[cfg(test)]
+mod instantiation_time_test {
+    use std::time::Duration;
+
+    use crate::{
+        test_support::{answer, EmptyView},
+        RuntimeBuilder, RuntimeError,
+    };
+
+    const SLOW_START: &str = r#"
+        (component
+            (core module $m
+                (func $spin (loop $l (br $l)))
+                (start $spin))
+            (core instance $i (instantiate $m))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_times_out_slow_instantiation() {
+        let limit = Duration::from_millis(50);
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .max_instantiation_time(limit)
+            .build()
+            .expect("Failed to build runtime");
+
+        let slow = runtime
+            .load_component(SLOW_START)
+            .expect("failed to compile component");
+        let err = runtime
+            .instantiate_component(&slow)
+            .await
+            .expect_err("instantiation should time out");
+        match err {
+            RuntimeError::InstantiationTimedOut {
+                max_instantiation_time,
+            } => assert_eq!(max_instantiation_time, limit),
+            other => panic!("expected an instantiation timeout, got {other:?}"),
+        }
+
+        let answer = runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &answer)
+            .await
+            .expect("failed to instantiate component");
+        tokio::time::sleep(limit * 2).await;
+        runtime
+            .call_named("plugin", "answer", &[])
+            .await
+            .expect("calls are not bound by the instantiation limit");
+    }
+}
+
+#[cfg(test)]
+mod call_timeout_test {
+    use std::time::Duration;
+
+    use crate::{
+        test_support::{EmptyView, LOOP_FOREVER},
+        RuntimeBuilder, RuntimeError,
+    };
+
+    #[tokio::test]
+    async fn it_times_out_long_calls() {
+        let timeout = Duration::from_millis(50);
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_timeout(timeout)
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(LOOP_FOREVER)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("instantiation is not bound by the call timeout");
+
+        let err = runtime
+            .call_named("plugin", "spin", &[])
+            .await
+            .expect_err("spin should time out");
+        match err {
+            RuntimeError::Timeout { timeout: limit } => assert_eq!(limit, timeout),
+            other => panic!("expected a timeout, got {other:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod call_cancellable_test {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::{
+        test_support::{answer, EmptyView, LOOP_FOREVER},
+        RuntimeBuilder, RuntimeError,
+    };
+
+    #[tokio::test]
+    async fn it_cancels_spinning_calls() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .cancellable()
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(LOOP_FOREVER)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        let token = CancellationToken::new();
+        thread::spawn({
+            let token = token.clone();
+            move || {
+                thread::sleep(Duration::from_millis(50));
+                token.cancel();
+            }
+        });
+
+        let started = Instant::now();
+        let err = runtime
+            .call_cancellable("plugin", "spin", &[], &token)
+            .await
+            .expect_err("spin should be cancelled");
+        assert!(matches!(err, RuntimeError::Cancelled));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn it_stops_watching_tokens_of_dropped_calls() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .cancellable()
+            .poll_budget(Duration::from_millis(1))
+            .build()
+            .expect("Failed to build runtime");
+
+        let spin = runtime
+            .load_component(LOOP_FOREVER)
+            .expect("failed to compile component");
+        let answer = runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("spin", &spin)
+            .await
+            .expect("failed to instantiate component");
+        runtime
+            .instantiate_named("answer", &answer)
+            .await
+            .expect("failed to instantiate component");
+
+        let token = CancellationToken::new();
+        let call = runtime.call_cancellable("spin", "spin", &[], &token);
+        tokio::time::timeout(Duration::from_millis(50), call)
+            .await
+            .expect_err("spin should still be running");
+
+        token.cancel();
+        runtime
+            .call_named("answer", "answer", &[])
+            .await
+            .expect("the dropped call's token should no longer be watched");
+    }
+}
@@ -0,0 +1,55 @@
+use wasmtime::{
+    component::{Component, Instance},
+    AsContextMut,
+};
+
+use crate::{NestedView, Runtime, RuntimeError};
+
+/// A typed view of an instance's exports, such as the world struct generated by `bindgen!`.
+///
+/// Generated worlds don't share a trait, so implement this with
+/// [`instantiable!`](crate::instantiable), which forwards to the generated `new` constructor:
+///
+/// ```ignore
+/// wasmtime::component::bindgen!({ path: "wit", world: "example", async: true });
+/// its_wasmtime::instantiable!(Example);
+///
+/// let example: Example = runtime.instantiate(&component).await?;
+/// ```
+pub trait Instantiable: Sized {
+    fn from_instance(store: impl AsContextMut, instance: &Instance) -> anyhow::Result<Self>;
+}
+
+/// Implements [`Instantiable`] for `bindgen!` generated worlds.
+#[macro_export]
+macro_rules! instantiable {
+    ($($world:ty),+ $(,)?) => {
+        $(
+            impl $crate::Instantiable for $world {
+                fn from_instance(
+                    store: impl $crate::wasmtime::AsContextMut,
+                    instance: &$crate::wasmtime::component::Instance,
+                ) -> $crate::anyhow::Result<Self> {
+                    <$world>::new(store, instance)
+                }
+            }
+        )+
+    };
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Instantiates `component` and wraps it in the typed bindings `W`.
+    ///
+    /// This goes through [`Runtime::instantiate_component`], so quarantine, instantiation time
+    /// limits and events apply as usual.
+    pub async fn instantiate<W: Instantiable>(
+        &mut self,
+        component: &Component,
+    ) -> Result<W, RuntimeError> {
+        let instance = self.instantiate_component(component).await?;
+        W::from_instance(&mut self.store, &instance).map_err(RuntimeError::Instantiation)
+    }
+}
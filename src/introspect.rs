@@ -0,0 +1,539 @@
+use std::collections::BTreeMap;
+
+use wasmtime::{
+    component::{
+        types::{ComponentFunc, ComponentItem},
+        Component, ResourceType, Type,
+    },
+    Engine,
+};
+
+use crate::{NestedView, Runtime, RuntimeError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Imported,
+    Exported,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceFunctionKind {
+    Constructor,
+    Method,
+    Static,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceFunctionInfo {
+    pub name: String,
+    pub kind: ResourceFunctionKind,
+    pub params: Vec<String>,
+    pub results: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceTypeInfo {
+    /// The instance the resource is declared in, or `None` for resources at the component root.
+    pub interface: Option<String>,
+    pub name: String,
+    pub direction: Direction,
+    pub functions: Vec<ResourceFunctionInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Function,
+    Instance,
+    Resource,
+    Type,
+    Module,
+    Component,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportInfo {
+    pub name: String,
+    pub kind: ItemKind,
+    /// What an imported instance has to export, empty for other kinds.
+    pub exports: Vec<ExportInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportInfo {
+    pub name: String,
+    pub kind: ItemKind,
+    /// What a nested instance exports in turn, empty for other kinds.
+    pub exports: Vec<ExportInfo>,
+}
+
+/// Differences between the imports and exports of two components.
+///
+/// Items are identified by their path, e.g. `wasi:cli/stdout#get-stdout`, and compared by
+/// signature.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterfaceDiff {
+    pub added_exports: Vec<String>,
+    pub removed_exports: Vec<String>,
+    pub changed_exports: Vec<String>,
+    pub added_imports: Vec<String>,
+    pub removed_imports: Vec<String>,
+    pub changed_imports: Vec<String>,
+}
+
+impl InterfaceDiff {
+    /// Whether hosts or callers of the old component may break against the new one.
+    ///
+    /// Removed or changed exports break callers, and added or changed imports may not be
+    /// satisfied by a host configured for the old component. Added exports and removed imports
+    /// are compatible.
+    pub fn is_breaking(&self) -> bool {
+        !self.removed_exports.is_empty()
+            || !self.changed_exports.is_empty()
+            || !self.added_imports.is_empty()
+            || !self.changed_imports.is_empty()
+    }
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Compares the interfaces of two component binaries.
+    pub fn interface_diff(
+        &self,
+        old_bytes: &[u8],
+        new_bytes: &[u8],
+    ) -> anyhow::Result<InterfaceDiff> {
+        let old = Component::new(&self.engine, old_bytes)?.component_type();
+        let new = Component::new(&self.engine, new_bytes)?.component_type();
+
+        let (added_imports, removed_imports, changed_imports) = diff_items(
+            flatten_items(&self.engine, old.imports(&self.engine)),
+            flatten_items(&self.engine, new.imports(&self.engine)),
+        );
+        let (added_exports, removed_exports, changed_exports) = diff_items(
+            flatten_items(&self.engine, old.exports(&self.engine)),
+            flatten_items(&self.engine, new.exports(&self.engine)),
+        );
+
+        Ok(InterfaceDiff {
+            added_exports,
+            removed_exports,
+            changed_exports,
+            added_imports,
+            removed_imports,
+            changed_imports,
+        })
+    }
+
+    /// Lists what a component exports, descending into exported instances, without
+    /// instantiating it.
+    pub fn component_exports(&self, component: &Component) -> Vec<ExportInfo> {
+        collect_exports(
+            &self.engine,
+            component.component_type().exports(&self.engine),
+        )
+    }
+
+    /// Lists what a component imports, i.e. what the linker has to provide to instantiate it.
+    pub fn component_imports(&self, component: &Component) -> Vec<ImportInfo> {
+        collect_exports(
+            &self.engine,
+            component.component_type().imports(&self.engine),
+        )
+        .into_iter()
+        .map(|export| ImportInfo {
+            name: export.name,
+            kind: export.kind,
+            exports: export.exports,
+        })
+        .collect()
+    }
+
+    /// Checks that this runtime's linker satisfies every import of `component`, so a host can
+    /// fail fast before instantiating it.
+    ///
+    /// Imports the linker doesn't define are reported as [`RuntimeError::MissingImports`]. If all
+    /// of them are defined but an instance lacks an export or an item has the wrong type, the
+    /// linker's error is returned as [`RuntimeError::LinkerSetup`].
+    pub fn validate(&self, component: &Component) -> Result<(), RuntimeError> {
+        let imports = self.missing_imports(component);
+        if !imports.is_empty() {
+            return Err(RuntimeError::MissingImports { imports });
+        }
+
+        self.linker
+            .instantiate_pre(component)
+            .map(|_| ())
+            .map_err(RuntimeError::LinkerSetup)
+    }
+
+    /// Names the top-level imports of `component` that this runtime's linker doesn't define.
+    ///
+    /// Instances are only checked by name, an instance missing some of its exports is not
+    /// reported.
+    pub(crate) fn missing_imports(&self, component: &Component) -> Vec<String> {
+        let mut probe = self.linker.clone();
+        let mut root = probe.root();
+
+        component
+            .component_type()
+            .imports(&self.engine)
+            .filter(|(name, item)| {
+                // Defining an item the linker already has fails, which is what tells us it's there.
+                let defined = match item {
+                    ComponentItem::ComponentInstance(_) => root.instance(name).is_err(),
+                    ComponentItem::ComponentFunc(_) => {
+                        root.func_new(name, |_, _, _| Ok(())).is_err()
+                    }
+                    ComponentItem::Resource(_) => root
+                        .resource(name, ResourceType::host::<()>(), |_, _| Ok(()))
+                        .is_err(),
+                    _ => true,
+                };
+                !defined
+            })
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Lists the resource types a component imports and exports, along with their functions.
+    pub fn list_resources(&self, component: &Component) -> Vec<ResourceTypeInfo> {
+        let ty = component.component_type();
+        let mut resources = Vec::new();
+
+        for (name, item) in ty.imports(&self.engine) {
+            collect_resources(
+                &self.engine,
+                None,
+                name,
+                item,
+                Direction::Imported,
+                &mut resources,
+            );
+        }
+        for (name, item) in ty.exports(&self.engine) {
+            collect_resources(
+                &self.engine,
+                None,
+                name,
+                item,
+                Direction::Exported,
+                &mut resources,
+            );
+        }
+
+        resources
+    }
+}
+
+fn collect_exports<'a>(
+    engine: &Engine,
+    items: impl Iterator<Item = (&'a str, ComponentItem)>,
+) -> Vec<ExportInfo> {
+    items
+        .map(|(name, item)| {
+            let (kind, exports) = match item {
+                ComponentItem::ComponentInstance(instance) => (
+                    ItemKind::Instance,
+                    collect_exports(engine, instance.exports(engine)),
+                ),
+                ComponentItem::ComponentFunc(_) | ComponentItem::CoreFunc(_) => {
+                    (ItemKind::Function, Vec::new())
+                }
+                ComponentItem::Resource(_) => (ItemKind::Resource, Vec::new()),
+                ComponentItem::Type(_) => (ItemKind::Type, Vec::new()),
+                ComponentItem::Module(_) => (ItemKind::Module, Vec::new()),
+                ComponentItem::Component(_) => (ItemKind::Component, Vec::new()),
+            };
+            ExportInfo {
+                name: name.into(),
+                kind,
+                exports,
+            }
+        })
+        .collect()
+}
+
+fn collect_resources(
+    engine: &Engine,
+    interface: Option<&str>,
+    name: &str,
+    item: ComponentItem,
+    direction: Direction,
+    resources: &mut Vec<ResourceTypeInfo>,
+) {
+    match item {
+        ComponentItem::Resource(_) => resources.push(ResourceTypeInfo {
+            interface: interface.map(String::from),
+            name: name.into(),
+            direction,
+            functions: Vec::new(),
+        }),
+        ComponentItem::ComponentFunc(func) => {
+            let Some((kind, resource, function)) = parse_resource_function(name) else {
+                return;
+            };
+            let Some(info) = resources.iter_mut().rev().find(|r| {
+                r.name == resource
+                    && r.direction == direction
+                    && r.interface.as_deref() == interface
+            }) else {
+                return;
+            };
+            info.functions.push(ResourceFunctionInfo {
+                name: function.into(),
+                kind,
+                params: func.params().map(|p| describe_type(&p)).collect(),
+                results: func.results().map(|r| describe_type(&r)).collect(),
+            });
+        }
+        ComponentItem::ComponentInstance(instance) => {
+            let mut functions = Vec::new();
+            for (export, item) in instance.exports(engine) {
+                // Resource functions can be exported before their resource, so link them last.
+                if matches!(item, ComponentItem::ComponentFunc(_)) {
+                    functions.push((export, item));
+                } else {
+                    collect_resources(engine, Some(name), export, item, direction, resources);
+                }
+            }
+            for (export, item) in functions {
+                collect_resources(engine, Some(name), export, item, direction, resources);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse_resource_function(name: &str) -> Option<(ResourceFunctionKind, &str, &str)> {
+    if let Some(resource) = name.strip_prefix("[constructor]") {
+        return Some((ResourceFunctionKind::Constructor, resource, resource));
+    }
+
+    let (kind, rest) = if let Some(rest) = name.strip_prefix("[method]") {
+        (ResourceFunctionKind::Method, rest)
+    } else if let Some(rest) = name.strip_prefix("[static]") {
+        (ResourceFunctionKind::Static, rest)
+    } else {
+        return None;
+    };
+
+    let (resource, function) = rest.split_once('.')?;
+    Some((kind, resource, function))
+}
+
+fn flatten_items<'a>(
+    engine: &Engine,
+    items: impl Iterator<Item = (&'a str, ComponentItem)>,
+) -> BTreeMap<String, String> {
+    let mut flattened = BTreeMap::new();
+    for (name, item) in items {
+        flatten_item(engine, name.into(), item, &mut flattened);
+    }
+    flattened
+}
+
+fn flatten_item(
+    engine: &Engine,
+    path: String,
+    item: ComponentItem,
+    flattened: &mut BTreeMap<String, String>,
+) {
+    let signature = match item {
+        ComponentItem::ComponentInstance(instance) => {
+            for (name, item) in instance.exports(engine) {
+                flatten_item(engine, format!("{path}#{name}"), item, flattened);
+            }
+            "instance".into()
+        }
+        ComponentItem::ComponentFunc(func) => describe_func(&func),
+        ComponentItem::CoreFunc(func) => format!("core func {func:?}"),
+        ComponentItem::Module(_) => "module".into(),
+        ComponentItem::Component(_) => "component".into(),
+        ComponentItem::Type(ty) => describe_type(&ty),
+        ComponentItem::Resource(_) => "resource".into(),
+    };
+    flattened.insert(path, signature);
+}
+
+fn diff_items(
+    old: BTreeMap<String, String>,
+    new: BTreeMap<String, String>,
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let added = new
+        .keys()
+        .filter(|k| !old.contains_key(*k))
+        .cloned()
+        .collect();
+    let removed = old
+        .keys()
+        .filter(|k| !new.contains_key(*k))
+        .cloned()
+        .collect();
+    let changed = old
+        .iter()
+        .filter(|(k, sig)| new.get(*k).is_some_and(|new_sig| new_sig != *sig))
+        .map(|(k, _)| k.clone())
+        .collect();
+    (added, removed, changed)
+}
+
+fn describe_func(func: &ComponentFunc) -> String {
+    let params: Vec<_> = func.params().map(|p| describe_type(&p)).collect();
+    let results: Vec<_> = func.results().map(|r| describe_type(&r)).collect();
+    format!("func({}) -> ({})", params.join(", "), results.join(", "))
+}
+
+/// Describes a type structurally, so equal shapes from different components compare equal.
+pub(crate) fn describe_type(ty: &Type) -> String {
+    let join = |types: Vec<String>| types.join(", ");
+    match ty {
+        Type::List(list) => format!("list<{}>", describe_type(&list.ty())),
+        Type::Record(record) => format!(
+            "record {{ {} }}",
+            join(
+                record
+                    .fields()
+                    .map(|f| format!("{}: {}", f.name, describe_type(&f.ty)))
+                    .collect()
+            )
+        ),
+        Type::Tuple(tuple) => format!(
+            "tuple<{}>",
+            join(tuple.types().map(|t| describe_type(&t)).collect())
+        ),
+        Type::Variant(variant) => format!(
+            "variant {{ {} }}",
+            join(
+                variant
+                    .cases()
+                    .map(|c| match c.ty {
+                        Some(ty) => format!("{}({})", c.name, describe_type(&ty)),
+                        None => c.name.to_string(),
+                    })
+                    .collect()
+            )
+        ),
+        Type::Enum(e) => format!("enum {{ {} }}", join(e.names().map(String::from).collect())),
+        Type::Option(option) => format!("option<{}>", describe_type(&option.ty())),
+        Type::Result(result) => {
+            let describe = |ty: Option<Type>| ty.map_or("_".into(), |ty| describe_type(&ty));
+            format!(
+                "result<{}, {}>",
+                describe(result.ok()),
+                describe(result.err())
+            )
+        }
+        Type::Flags(flags) => format!(
+            "flags {{ {} }}",
+            join(flags.names().map(String::from).collect())
+        ),
+        Type::Own(_) => "own<resource>".into(),
+        Type::Borrow(_) => "borrow<resource>".into(),
+        primitive => format!("{primitive:?}").to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod component_exports_test {
+    use crate::{runtime, test_support::EmptyView, ExportInfo, ItemKind};
+
+    const EXPORTS: &str = r#"
+        (component
+            (core module $m (func (export "f") (result i32) i32.const 7))
+            (core instance $i (instantiate $m))
+            (func $answer (result u32) (canon lift (core func $i "f")))
+            (type $thing (resource (rep i32)))
+            (instance $inner (export "answer" (func $answer)))
+            (export "thing" (type $thing))
+            (export "inner" (instance $inner))
+            (export "answer" (func $answer))
+        )
+    "#;
+
+    fn leaf(name: &str, kind: ItemKind) -> ExportInfo {
+        ExportInfo {
+            name: name.into(),
+            kind,
+            exports: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn it_lists_nested_exports() {
+        let runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+        let component = runtime
+            .load_component(EXPORTS)
+            .expect("failed to compile component");
+
+        let mut exports = runtime.component_exports(&component);
+        exports.sort_by(|a, b| a.name.cmp(&b.name));
+
+        assert_eq!(
+            exports,
+            vec![
+                leaf("answer", ItemKind::Function),
+                ExportInfo {
+                    name: "inner".into(),
+                    kind: ItemKind::Instance,
+                    exports: vec![leaf("answer", ItemKind::Function)],
+                },
+                leaf("thing", ItemKind::Resource),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod interface_diff_test {
+    use crate::{runtime, test_support::EmptyView};
+
+    fn component(exports: &[&str]) -> String {
+        let funcs: String = exports
+            .iter()
+            .map(|name| {
+                format!(r#"(func (export "{name}") (result u32) (canon lift (core func $i "f")))"#)
+            })
+            .collect();
+        format!(
+            r#"(component
+                (core module $m (func (export "f") (result i32) i32.const 0))
+                (core instance $i (instantiate $m))
+                {funcs}
+            )"#
+        )
+    }
+
+    #[test]
+    fn it_flags_removed_exports() {
+        let runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let diff = runtime
+            .interface_diff(
+                component(&["a", "b"]).as_bytes(),
+                component(&["a", "c"]).as_bytes(),
+            )
+            .expect("failed to diff components");
+
+        assert_eq!(diff.added_exports, vec!["c".to_string()]);
+        assert_eq!(diff.removed_exports, vec!["b".to_string()]);
+        assert!(diff.changed_exports.is_empty());
+        assert!(diff.is_breaking());
+    }
+
+    #[test]
+    fn it_accepts_added_exports() {
+        let runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let diff = runtime
+            .interface_diff(
+                component(&["a"]).as_bytes(),
+                component(&["a", "b"]).as_bytes(),
+            )
+            .expect("failed to diff components");
+
+        assert_eq!(diff.added_exports, vec!["b".to_string()]);
+        assert!(!diff.is_breaking());
+    }
+}
@@ -0,0 +1,159 @@
+use wasmtime::{
+    component::{Component, Instance, Val},
+    Store,
+};
+use wasmtime_wasi::pipe::MemoryInputPipe;
+
+use crate::{stdio::CapturedPipe, NestedView, Runtime, RuntimeError, RuntimeView};
+
+/// An instance living in its own store, with its own nested view, WASI context and resource
+/// table, created by [`Runtime::spawn_instance`].
+///
+/// It doesn't borrow the runtime, so many of them can run concurrently, e.g. one per request.
+pub struct IsolatedInstance<T: NestedView> {
+    pub store: Store<RuntimeView<T>>,
+    pub instance: Instance,
+    max_wasm_stack: usize,
+    captured_stdout: Option<CapturedPipe>,
+    captured_stderr: Option<CapturedPipe>,
+}
+
+impl<T> IsolatedInstance<T>
+where
+    T: NestedView,
+{
+    /// Calls the function `export` with dynamically typed arguments, classifying failures like
+    /// [`Runtime::classify_error`] does.
+    pub async fn call(&mut self, export: &str, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+        let func = self
+            .instance
+            .get_func(&mut self.store, export)
+            .ok_or_else(|| anyhow::anyhow!("instance has no export `{export}`"))?;
+
+        let mut results = vec![Val::Bool(false); func.results(&self.store).len()];
+        let outcome = async {
+            func.call_async(&mut self.store, args, &mut results).await?;
+            func.post_return_async(&mut self.store).await
+        }
+        .await;
+        match outcome {
+            Ok(()) => Ok(results),
+            Err(err) => Err(RuntimeError::classify(err, self.max_wasm_stack)),
+        }
+    }
+
+    /// Returns what the guest wrote to stdout since the last call, or nothing if the runtime
+    /// doesn't capture stdout. Each instance captures its own output.
+    pub fn take_stdout(&mut self) -> Vec<u8> {
+        self.captured_stdout
+            .as_mut()
+            .map(CapturedPipe::take)
+            .unwrap_or_default()
+    }
+
+    /// Returns what the guest wrote to stderr since the last call, or nothing if the runtime
+    /// doesn't capture stderr.
+    pub fn take_stderr(&mut self) -> Vec<u8> {
+        self.captured_stderr
+            .as_mut()
+            .map(CapturedPipe::take)
+            .unwrap_or_default()
+    }
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Instantiates `component` into a fresh store holding `nested_view`, reusing this runtime's
+    /// engine and linker, so a component compiled once can serve many isolated tenants.
+    ///
+    /// The new store gets its own WASI context and resource table, set up like the runtime's own:
+    /// args, env, preopens, clocks, random, network access, memory and table limits and fuel (the
+    /// amount the runtime was built with) carry over. If the runtime captures stdout or stderr,
+    /// the instance captures its own, read with [`IsolatedInstance::take_stdout`] and
+    /// [`IsolatedInstance::take_stderr`]. A [`crate::RuntimeBuilder::stdin_pipe`] only feeds the
+    /// runtime's own store, so spawned instances read an empty stdin. Epoch interruption carries
+    /// over too, but timeouts and quarantine only apply to the runtime's own store.
+    pub async fn spawn_instance(
+        &self,
+        component: &Component,
+        nested_view: T,
+    ) -> Result<IsolatedInstance<T>, RuntimeError> {
+        let settings = &self.store_settings;
+        let mut wasi_ctx = settings.wasi_ctx()?;
+        if settings.stdin_piped {
+            wasi_ctx.stdin(MemoryInputPipe::new(Vec::<u8>::new()));
+        }
+        let captured_stdout = settings.capture_stdout.then(CapturedPipe::new);
+        if let Some(captured) = &captured_stdout {
+            wasi_ctx.stdout(captured.pipe());
+        }
+        let captured_stderr = settings.capture_stderr.then(CapturedPipe::new);
+        if let Some(captured) = &captured_stderr {
+            wasi_ctx.stderr(captured.pipe());
+        }
+
+        let view = RuntimeView::with_ctx(nested_view, wasi_ctx.build());
+        let mut store = settings.store(&self.engine, view)?;
+        if let Some(fuel) = self.initial_fuel {
+            store.set_fuel(fuel)?;
+        }
+        if self.epoch_ticker.is_some() {
+            store.epoch_deadline_async_yield_and_update(1);
+        }
+
+        let instance = self
+            .linker
+            .instantiate_async(&mut store, component)
+            .await
+            .map_err(RuntimeError::instantiation)?;
+        Ok(IsolatedInstance {
+            store,
+            instance,
+            max_wasm_stack: self.max_wasm_stack,
+            captured_stdout,
+            captured_stderr,
+        })
+    }
+}
+
+#[cfg(test)]
+mod spawn_instance_test {
+    use wasmtime_wasi::bindings::Command;
+
+    use crate::{
+        test_support::{load_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_configures_spawned_stores_like_the_runtime() {
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .args(["cli_echo", "--tenant"])
+            .env("GREETING", "hello")
+            .capture_stdout()
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = load_guest(&runtime.engine, "cli_echo");
+        let mut spawned = runtime
+            .spawn_instance(&component, EmptyView)
+            .await
+            .expect("failed to spawn instance");
+        Command::new(&mut spawned.store, &spawned.instance)
+            .expect("missing command exports")
+            .wasi_cli_run()
+            .call_run(&mut spawned.store)
+            .await
+            .expect("failed to run command")
+            .expect("command failed");
+
+        let output = String::from_utf8(spawned.take_stdout()).unwrap();
+        assert!(output.starts_with("cli_echo\n--tenant\n"));
+        assert!(output.lines().any(|line| line == "GREETING=hello"));
+        assert!(runtime.take_stdout().is_empty());
+    }
+}
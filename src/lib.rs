@@ -1,24 +1,204 @@
-use wasmtime::{component::Linker, Config, Engine, Store};
-use wasmtime_wasi::{ResourceTable, WasiCtx, WasiCtxBuilder, WasiView};
+mod builder;
+mod clock;
+mod command;
+mod compose;
+mod config_store;
+mod epoch;
+mod error;
+mod events;
+mod footprint;
+mod fuel;
+mod global;
+mod host_timeout;
+mod instance;
+mod instantiable;
+mod introspect;
+mod isolate;
+mod link;
+mod load;
+mod progress;
+mod quarantine;
+mod rate_limit;
+mod resource;
+mod shim;
+mod stdio;
+mod store;
+mod trace;
+#[doc(hidden)]
+pub mod val;
+
+// Used by the exported macros, so they work in crates that don't depend on these directly.
+#[doc(hidden)]
+pub use anyhow;
+#[doc(hidden)]
+pub use wasmtime;
+
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::io::AsyncWrite;
+use wasmtime::{
+    component::{Instance, Linker, Resource, ResourceTableError},
+    Config, Engine, PoolingAllocationConfig, Store, StoreLimits,
+};
+use wasmtime_wasi::{
+    async_trait, pipe::AsyncWriteStream, OutputStream, ResourceTable, WasiCtx, WasiCtxBuilder,
+    WasiView,
+};
+use wasmtime_wasi_http::{WasiHttpCtx, WasiHttpView};
+
+pub use builder::{LinkerTimings, RuntimeBuilder};
+pub use command::{CommandOutput, COMMAND_CAPTURE_LIMIT};
+pub use compose::{HostInterface, InterfaceLinker};
+pub use config_store::CONFIG_STORE_INTERFACE;
+pub use error::RuntimeError;
+pub use events::RuntimeEvent;
+pub use footprint::FootprintEstimate;
+pub use global::{register_global_host_fn, unregister_global_host_fn};
+pub use host_timeout::with_host_timeout;
+pub use instantiable::Instantiable;
+pub use introspect::{
+    Direction, ExportInfo, ImportInfo, InterfaceDiff, ItemKind, ResourceFunctionInfo,
+    ResourceFunctionKind, ResourceTypeInfo,
+};
+pub use isolate::IsolatedInstance;
+pub use load::ArtifactInfo;
+pub use progress::PROGRESS_INTERFACE;
+pub use rate_limit::RateLimit;
+pub use resource::{Capability, OwnedResource};
+pub use shim::ImportShim;
+pub use stdio::{STDIN_PIPE_BUFFER, STDIO_CAPTURE_LIMIT};
+pub use val::{FromVal, ToVal};
+
+/// Bytes an output stream created by [`RuntimeView::new_output_stream`] may buffer.
+pub const OUTPUT_STREAM_BUDGET: usize = 64 * 1024;
 
 pub struct RuntimeView<T: NestedView> {
+    /// Holds the host side of every resource handed to guests, shared with WASI.
+    ///
+    /// Keep host resource state here rather than in a separate table: implement the bindgen
+    /// `Host` traits for `RuntimeView<V>` and pass `|view| view` to `add_to_linker`. Entries are
+    /// only reachable through handles in the owning instance's own handle table, so another
+    /// instance in the same store can't use them.
     pub table: ResourceTable,
     pub ctx: WasiCtx,
+    /// State for `wasi:http`, used when the runtime was built with
+    /// [`RuntimeBuilder::with_wasi_http`].
+    pub http: WasiHttpCtx,
     pub nested_view: T,
+    limits: StoreLimits,
+    shutdown_hooks: Vec<ShutdownHook>,
+    command_capture: Option<command::CommandCapture>,
 }
 
+type ShutdownHook = Box<dyn FnOnce(&mut ResourceTable) + Send>;
+
 impl<T> RuntimeView<T>
 where
     T: NestedView,
 {
+    #[cfg(test)]
     fn new(nested_view: T) -> Self {
+        Self::with_ctx(nested_view, WasiCtxBuilder::new().inherit_stdio().build())
+    }
+
+    fn with_ctx(nested_view: T, ctx: WasiCtx) -> Self {
         let table = ResourceTable::new();
-        let ctx = WasiCtxBuilder::new().inherit_stdio().build();
 
         Self {
             table,
             ctx,
+            http: WasiHttpCtx::new(),
             nested_view,
+            limits: StoreLimits::default(),
+            shutdown_hooks: Vec::new(),
+            command_capture: None,
+        }
+    }
+
+    /// Registers `hook` to tear down host resources when the view is dropped, which happens when
+    /// the runtime's store is dropped.
+    ///
+    /// Hooks run in reverse registration order, before any field of the view is dropped, so they
+    /// can still delete their entries from the table and release external handles (sockets,
+    /// files) explicitly. Entries left in the table afterwards are dropped with it, along with the
+    /// WASI context and then the nested view.
+    pub fn on_shutdown<F>(&mut self, hook: F)
+    where
+        F: FnOnce(&mut ResourceTable) + Send + 'static,
+    {
+        self.shutdown_hooks.push(Box::new(hook));
+    }
+
+    /// Wraps `resource` so its table entry is deleted when the wrapper is dropped.
+    pub fn own_resource<R>(&mut self, resource: Resource<R>) -> OwnedResource<'_, R>
+    where
+        R: 'static,
+    {
+        OwnedResource::new(&mut self.table, resource)
+    }
+
+    /// Hands out a `wasi:io/streams` output stream that forwards everything the guest writes to
+    /// `sink`, so large results can be consumed as they are produced.
+    ///
+    /// At most [`OUTPUT_STREAM_BUDGET`] bytes are buffered before the guest has to wait for the
+    /// sink to catch up. Must be called from within a tokio runtime.
+    pub fn new_output_stream<W>(
+        &mut self,
+        sink: W,
+    ) -> Result<Resource<OutputStream>, ResourceTableError>
+    where
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let stream: OutputStream = Box::new(AsyncWriteStream::new(OUTPUT_STREAM_BUDGET, sink));
+        self.table.push(stream)
+    }
+
+    /// Mints a capability handle for `value` that can be passed to the guest.
+    pub fn grant_capability<C>(
+        &mut self,
+        value: C,
+    ) -> Result<Resource<Capability<C>>, ResourceTableError>
+    where
+        C: Send + 'static,
+    {
+        self.table.push(Capability::new(value))
+    }
+
+    /// Validates a capability handle received from the guest and returns the granted value.
+    pub fn capability<C>(
+        &self,
+        capability: &Resource<Capability<C>>,
+    ) -> Result<&C, ResourceTableError>
+    where
+        C: Send + 'static,
+    {
+        self.table.get(capability).map(Capability::get)
+    }
+
+    /// Revokes a capability, so later uses of the handle fail, and returns the granted value.
+    pub fn revoke_capability<C>(
+        &mut self,
+        capability: Resource<Capability<C>>,
+    ) -> Result<C, ResourceTableError>
+    where
+        C: Send + 'static,
+    {
+        self.table.delete(capability).map(Capability::into_inner)
+    }
+}
+
+impl<T> Drop for RuntimeView<T>
+where
+    T: NestedView,
+{
+    fn drop(&mut self) {
+        while let Some(hook) = self.shutdown_hooks.pop() {
+            hook(&mut self.table);
         }
     }
 }
@@ -36,7 +216,28 @@ where
     }
 }
 
+impl<T> WasiHttpView for RuntimeView<T>
+where
+    T: Send + NestedView,
+{
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+
+    fn ctx(&mut self) -> &mut WasiHttpCtx {
+        &mut self.http
+    }
+}
+
+#[async_trait]
 pub trait NestedView: Send + Sized {
+    /// Async setup that has to happen before linking, e.g. opening a database connection that
+    /// host functions use. Called by [`RuntimeBuilder::build_async`] before
+    /// [`NestedView::add_all_to_linker`]; the synchronous [`RuntimeBuilder::build`] skips it.
+    async fn initialize(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     fn add_all_to_linker(&mut self, linker: &mut Linker<RuntimeView<Self>>) -> anyhow::Result<()>;
 }
 
@@ -44,43 +245,229 @@ pub struct Runtime<T: NestedView> {
     pub engine: Engine,
     pub linker: Linker<RuntimeView<T>>,
     pub store: Store<RuntimeView<T>>,
+    validation_policy: Option<load::ValidationPolicy>,
+    warm_concurrency: Option<NonZeroUsize>,
+    compilation_pool: Option<Arc<rayon::ThreadPool>>,
+    max_wasm_stack: usize,
+    named_instances: HashMap<String, Instance>,
+    export_fallback: Option<instance::ExportFallback>,
+    linker_timings: LinkerTimings,
+    shimmed_imports: Vec<String>,
+    created_at: Instant,
+    max_lifetime: Option<Duration>,
+    events: events::EventPublisher,
+    max_instantiation_time: Option<Duration>,
+    call_timeout: Option<Duration>,
+    deadline: epoch::Deadline,
+    quarantine: Option<quarantine::QuarantineTracker>,
+    captured_stdout: Option<stdio::CapturedPipe>,
+    captured_stderr: Option<stdio::CapturedPipe>,
+    fuel_added: Option<u64>,
+    initial_fuel: Option<u64>,
+    /// Only running when something needs epochs, and stopped and joined when the runtime is
+    /// dropped.
+    epoch_ticker: Option<epoch::EpochTicker>,
+    store_settings: store::StoreSettings,
 }
 
-pub fn runtime<T>(with_wasi: bool, mut nested_view: T) -> anyhow::Result<Runtime<T>>
+/// Builds a runtime with default settings, see [`RuntimeBuilder`] for everything else that can
+/// be configured.
+pub fn runtime<T>(with_wasi: bool, nested_view: T) -> Result<Runtime<T>, RuntimeError>
 where
     T: NestedView,
 {
-    let config = {
-        let mut config = Config::new();
-        config.wasm_component_model(true);
-        config.async_support(true);
-        config
-    };
+    RuntimeBuilder::new()
+        .with_wasi(with_wasi)
+        .nested_view(nested_view)
+        .build()
+}
+
+/// Like [`runtime`], but reuses `engine` so components compiled once can be instantiated in
+/// many runtimes.
+///
+/// The engine needs the component model and async support, e.g. by creating it from
+/// [`default_config`]; see [`RuntimeBuilder::engine`].
+pub fn runtime_with_engine<T>(
+    engine: &Engine,
+    with_wasi: bool,
+    nested_view: T,
+) -> Result<Runtime<T>, RuntimeError>
+where
+    T: NestedView,
+{
+    RuntimeBuilder::new()
+        .engine(engine.clone())
+        .with_wasi(with_wasi)
+        .nested_view(nested_view)
+        .build()
+}
+
+/// The engine configuration every runtime needs: the component model and async support.
+pub fn default_config() -> Config {
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    config.async_support(true);
+    config
+}
+
+/// A pooling allocator configuration for [`RuntimeBuilder::with_pooling_allocator`] sized for a
+/// moderate number of concurrent tenants: 100 component instances and 1,000 core instances,
+/// memories and tables, each memory capped at 64 MiB.
+pub fn default_pooling_config() -> PoolingAllocationConfig {
+    let mut pooling = PoolingAllocationConfig::default();
+    pooling
+        .total_component_instances(100)
+        .total_core_instances(1_000)
+        .total_memories(1_000)
+        .total_tables(1_000)
+        .memory_pages(1_024);
+    pooling
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// The store's whole view: the resource table, WASI and HTTP contexts and the nested view.
+    pub fn view(&self) -> &RuntimeView<T> {
+        self.store.data()
+    }
+
+    /// Mutable access to the store's whole view, e.g. to push a host resource into the table and
+    /// update the nested view in one borrow.
+    pub fn view_mut(&mut self) -> &mut RuntimeView<T> {
+        self.store.data_mut()
+    }
+
+    pub fn nested_view(&self) -> &T {
+        &self.store.data().nested_view
+    }
+
+    /// Mutable access to the nested view, e.g. to update host state between guest calls.
+    pub fn nested_view_mut(&mut self) -> &mut T {
+        &mut self.store.data_mut().nested_view
+    }
+
+    /// Total time it took to build the linker, see [`Runtime::linker_timings`] for a breakdown.
+    pub fn linker_build_time(&self) -> Duration {
+        self.linker_timings.total
+    }
+
+    pub fn linker_timings(&self) -> LinkerTimings {
+        self.linker_timings
+    }
+
+    /// Returns the contents of the custom section called `name`, if present.
+    ///
+    /// Sections on the outer component win over ones found in nested modules or components.
+    pub fn custom_section(&self, bytes: &[u8], name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut depth = 0usize;
+        let mut nested = None;
 
-    let engine = Engine::new(&config)?;
+        for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+            match payload? {
+                wasmparser::Payload::ModuleSection { .. }
+                | wasmparser::Payload::ComponentSection { .. } => depth += 1,
+                wasmparser::Payload::End(_) => depth = depth.saturating_sub(1),
+                wasmparser::Payload::CustomSection(reader) if reader.name() == name => {
+                    if depth == 0 {
+                        return Ok(Some(reader.data().to_vec()));
+                    }
+                    nested.get_or_insert_with(|| reader.data().to_vec());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(nested)
+    }
+}
 
-    let mut linker = Linker::new(&engine);
+#[cfg(test)]
+mod test_support {
+    use super::*;
+
+    pub struct EmptyView;
+
+    impl NestedView for EmptyView {
+        fn add_all_to_linker(&mut self, _: &mut Linker<RuntimeView<Self>>) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
 
-    if with_wasi {
-        wasmtime_wasi::add_to_linker_async(&mut linker)?;
+    /// Component whose `answer` export returns `value`.
+    pub fn answer(value: u32) -> String {
+        format!(
+            r#"(component
+                (core module $m (func (export "f") (result i32) i32.const {value}))
+                (core instance $i (instantiate $m))
+                (func (export "answer") (result u32) (canon lift (core func $i "f")))
+            )"#
+        )
     }
 
-    nested_view.add_all_to_linker(&mut linker)?;
+    /// Component whose `spin` export never returns.
+    pub const LOOP_FOREVER: &str = r#"
+        (component
+            (core module $m
+                (func (export "spin") (result i32) (loop $l (br $l)) unreachable))
+            (core instance $i (instantiate $m))
+            (func (export "spin") (result u32) (canon lift (core func $i "spin")))
+        )
+    "#;
 
-    let runtime_view = RuntimeView::new(nested_view);
-    let store = Store::new(&engine, runtime_view);
+    /// Component whose `spin` export loops `n` times and returns `n`.
+    pub const SPIN: &str = r#"
+        (component
+            (core module $m
+                (func (export "spin") (param i32) (result i32)
+                    (local $i i32)
+                    (loop $l
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if $l (i32.lt_u (local.get $i) (local.get 0))))
+                    (local.get $i)))
+            (core instance $i (instantiate $m))
+            (func (export "spin") (param "n" u32) (result u32)
+                (canon lift (core func $i "spin")))
+        )
+    "#;
 
-    Ok(Runtime {
-        engine,
-        linker,
-        store,
-    })
+    /// Loads the guest crate in `tests/<guest>`, which has to be built beforehand.
+    pub fn load_guest(engine: &Engine, guest: &str) -> wasmtime::component::Component {
+        wasmtime::component::Component::from_file(
+            engine,
+            format!("./tests/{guest}/target/wasm32-wasi/debug/{guest}.wasm"),
+        )
+        .expect(
+            "Failed to load component from disk. Did you compile it using `cargo component build`?",
+        )
+    }
+
+    /// Runs the guest crate in `tests/<guest>` as a WASI command, failing if it traps or exits
+    /// with a failure status.
+    pub async fn run_guest<T: NestedView>(runtime: &mut Runtime<T>, guest: &str) -> Result<(), ()> {
+        let component = load_guest(&runtime.engine, guest);
+
+        let (command, _) = wasmtime_wasi::bindings::Command::instantiate_async(
+            &mut runtime.store,
+            &component,
+            &runtime.linker,
+        )
+        .await
+        .expect("failed to instantiate component");
+        command
+            .wasi_cli_run()
+            .call_run(&mut runtime.store)
+            .await
+            .map_err(|_| ())?
+    }
 }
 
 #[cfg(test)]
 mod simple_component_test {
     use super::*;
-    use wasmtime::{component::Component, AsContextMut};
+    use crate::test_support::load_guest;
+    use wasmtime::AsContextMut;
     use wasmtime_wasi::async_trait;
 
     wasmtime::component::bindgen!({
@@ -89,6 +476,8 @@ mod simple_component_test {
         async: true,
     });
 
+    crate::instantiable!(Example);
+
     struct SimpleComponentView {
         message: String,
     }
@@ -105,7 +494,9 @@ mod simple_component_test {
             &mut self,
             linker: &mut Linker<RuntimeView<Self>>,
         ) -> anyhow::Result<()> {
-            Ok(host::add_to_linker(linker, |v| &mut v.nested_view)?)
+            InterfaceLinker::new(linker)
+                .link(host::add_to_linker)
+                .finish()
         }
     }
 
@@ -117,13 +508,7 @@ mod simple_component_test {
 
         let mut runtime = runtime(true, nested_view).expect("Failed to build runtime");
 
-        let component = Component::from_file(
-            &runtime.engine,
-            "./tests/simple_component/target/wasm32-wasi/debug/simple_component.wasm",
-        )
-        .expect(
-            "Failed to load component from disk. Did you compile it using `cargo component build`?",
-        );
+        let component = load_guest(&runtime.engine, "simple_component");
 
         let (instance, _) =
             Example::instantiate_async(&mut runtime.store, &component, &runtime.linker)
@@ -148,6 +533,96 @@ mod simple_component_test {
 
         assert_eq!(result, "Hello, World! 1");
     }
+
+    #[tokio::test]
+    async fn it_observes_nested_view_changes() {
+        let nested_view = SimpleComponentView {
+            message: "Hello, World!".into(),
+        };
+
+        let mut runtime = runtime(true, nested_view).expect("Failed to build runtime");
+
+        let component = load_guest(&runtime.engine, "simple_component");
+
+        let (instance, _) =
+            Example::instantiate_async(&mut runtime.store, &component, &runtime.linker)
+                .await
+                .expect("failed to instantiate component");
+
+        let result = instance
+            .call_hello_world(&mut runtime.store)
+            .await
+            .expect("failed to invoke demo function");
+        assert_eq!(result, "Hello, World! 0");
+
+        runtime.nested_view_mut().message = "Goodbye!".into();
+        assert_eq!(runtime.nested_view().message, "Goodbye!");
+
+        let result = instance
+            .call_hello_world(&mut runtime.store)
+            .await
+            .expect("failed to invoke demo function");
+        assert_eq!(result, "Goodbye! 1");
+    }
+
+    #[tokio::test]
+    async fn it_instantiates_typed_bindings() {
+        let nested_view = SimpleComponentView {
+            message: "Hello, World!".into(),
+        };
+
+        let mut runtime = runtime(true, nested_view).expect("Failed to build runtime");
+
+        let component = load_guest(&runtime.engine, "simple_component");
+
+        let instance: Example = runtime
+            .instantiate(&component)
+            .await
+            .expect("failed to instantiate component");
+
+        let result = instance
+            .call_hello_world(&mut runtime.store)
+            .await
+            .expect("failed to invoke demo function");
+        assert_eq!(result, "Hello, World! 0");
+    }
+
+    #[tokio::test]
+    async fn it_runs_isolated_instances_concurrently() {
+        let runtime = runtime(
+            true,
+            SimpleComponentView {
+                message: "unused".into(),
+            },
+        )
+        .expect("Failed to build runtime");
+
+        let component = load_guest(&runtime.engine, "simple_component");
+
+        let spawn = |message: &str| {
+            runtime.spawn_instance(
+                &component,
+                SimpleComponentView {
+                    message: message.into(),
+                },
+            )
+        };
+        let (first, second) = tokio::join!(spawn("Hello"), spawn("Goodbye"));
+        let mut first = first.expect("failed to spawn instance");
+        let mut second = second.expect("failed to spawn instance");
+
+        let first_bindings =
+            Example::new(&mut first.store, &first.instance).expect("missing exports");
+        let second_bindings =
+            Example::new(&mut second.store, &second.instance).expect("missing exports");
+
+        let (first_result, second_result) = tokio::join!(
+            first_bindings.call_hello_world(&mut first.store),
+            second_bindings.call_hello_world(&mut second.store),
+        );
+        assert_eq!(first_result.unwrap(), "Hello 0");
+        assert_eq!(second_result.unwrap(), "Goodbye 0");
+    }
 }
 
 #[cfg(test)]
@@ -155,8 +630,8 @@ mod simple_resource_test {
     use self::component::simple_resource;
 
     use super::*;
+    use crate::test_support::load_guest;
     use anyhow::Ok;
-    use wasmtime::component::Component;
     use wasmtime_wasi::async_trait;
 
     wasmtime::component::bindgen!({
@@ -172,23 +647,21 @@ mod simple_resource_test {
         message: String,
     }
 
-    pub struct ResourceView {
-        table: ResourceTable,
-    }
+    pub struct ResourceView;
 
     impl NestedView for ResourceView {
         fn add_all_to_linker(
             &mut self,
             linker: &mut Linker<RuntimeView<Self>>,
         ) -> anyhow::Result<()> {
-            simple_resource::some_resource::add_to_linker(linker, |v| &mut v.nested_view)
+            simple_resource::some_resource::add_to_linker(linker, |v| v)
         }
     }
 
-    impl simple_resource::some_resource::Host for ResourceView {}
+    impl simple_resource::some_resource::Host for RuntimeView<ResourceView> {}
 
     #[async_trait]
-    impl simple_resource::some_resource::HostFooResource for ResourceView {
+    impl simple_resource::some_resource::HostFooResource for RuntimeView<ResourceView> {
         async fn foo(
             &mut self,
             this: wasmtime::component::Resource<simple_resource::some_resource::FooResource>,
@@ -219,19 +692,9 @@ mod simple_resource_test {
 
     #[tokio::test]
     async fn test() {
-        let nested_view = ResourceView {
-            table: ResourceTable::new(),
-        };
+        let mut runtime = runtime(true, ResourceView).expect("Failed to build runtime");
 
-        let mut runtime = runtime(true, nested_view).expect("Failed to build runtime");
-
-        let component = Component::from_file(
-            &runtime.engine,
-            "./tests/simple_resource/target/wasm32-wasi/debug/simple_resource.wasm",
-        )
-        .expect(
-            "Failed to load component from disk. Did you compile it using `cargo component build`?",
-        );
+        let component = load_guest(&runtime.engine, "simple_resource");
 
         let (instance, _) =
             Example::instantiate_async(&mut runtime.store, &component, &runtime.linker)
@@ -244,4 +707,606 @@ mod simple_resource_test {
             .expect("failed to invoke");
         assert_eq!(result, "Hello, World! noodles")
     }
+
+    #[test]
+    fn it_lists_resources() {
+        let runtime = runtime(true, ResourceView).expect("Failed to build runtime");
+
+        let component = load_guest(&runtime.engine, "simple_resource");
+
+        let resources = runtime.list_resources(&component);
+        assert_eq!(resources.len(), 1);
+
+        let resource = &resources[0];
+        assert_eq!(resource.name, "foo-resource");
+        assert_eq!(
+            resource.interface.as_deref(),
+            Some("component:simple-resource/some-resource")
+        );
+        assert_eq!(resource.direction, Direction::Imported);
+
+        let kinds: Vec<_> = resource
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.kind))
+            .collect();
+        assert!(kinds.contains(&("foo", ResourceFunctionKind::Method)));
+        assert!(kinds.contains(&("new", ResourceFunctionKind::Static)));
+    }
+
+    #[test]
+    fn it_lists_imports() {
+        let runtime = runtime(true, ResourceView).expect("Failed to build runtime");
+
+        let component = load_guest(&runtime.engine, "simple_resource");
+
+        let imports = runtime.component_imports(&component);
+        let import = imports
+            .iter()
+            .find(|i| i.name == "component:simple-resource/some-resource")
+            .expect("missing some-resource import");
+        assert_eq!(import.kind, ItemKind::Instance);
+        assert!(import
+            .exports
+            .iter()
+            .any(|e| e.name == "foo-resource" && e.kind == ItemKind::Resource));
+    }
+
+    #[tokio::test]
+    async fn it_names_missing_imports() {
+        let mut runtime =
+            runtime(true, crate::test_support::EmptyView).expect("Failed to build runtime");
+
+        let component = load_guest(&runtime.engine, "simple_resource");
+
+        match runtime.instantiate_component(&component).await {
+            Err(RuntimeError::MissingImports { imports }) => assert_eq!(
+                imports,
+                vec!["component:simple-resource/some-resource".to_string()]
+            ),
+            other => panic!("expected missing imports, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn it_validates_imports() {
+        let unlinked =
+            runtime(true, crate::test_support::EmptyView).expect("Failed to build runtime");
+        let component = load_guest(&unlinked.engine, "simple_resource");
+        match unlinked.validate(&component) {
+            Err(RuntimeError::MissingImports { imports }) => assert_eq!(
+                imports,
+                vec!["component:simple-resource/some-resource".to_string()]
+            ),
+            other => panic!("expected missing imports, got {other:?}"),
+        }
+
+        let linked = runtime(true, ResourceView).expect("Failed to build runtime");
+        let component = load_guest(&linked.engine, "simple_resource");
+        linked
+            .validate(&component)
+            .expect("linked runtime should satisfy the imports");
+    }
+}
+
+#[cfg(test)]
+mod custom_section_test {
+    use super::*;
+    use crate::test_support::EmptyView;
+
+    fn section(id: u8, contents: &[u8]) -> Vec<u8> {
+        assert!(
+            contents.len() < 0x80,
+            "test sections use a single byte length"
+        );
+        let mut bytes = vec![id, contents.len() as u8];
+        bytes.extend_from_slice(contents);
+        bytes
+    }
+
+    fn custom(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut contents = vec![name.len() as u8];
+        contents.extend_from_slice(name.as_bytes());
+        contents.extend_from_slice(data);
+        section(0, &contents)
+    }
+
+    const COMPONENT_HEADER: &[u8] = b"\0asm\x0d\0\x01\0";
+    const MODULE_HEADER: &[u8] = b"\0asm\x01\0\0\0";
+
+    #[test]
+    fn it_reads_custom_sections() {
+        let runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let mut module = MODULE_HEADER.to_vec();
+        module.extend(custom("manifest", b"nested"));
+        module.extend(custom("only-nested", b"inner"));
+
+        let mut component = COMPONENT_HEADER.to_vec();
+        component.extend(section(1, &module));
+        component.extend(custom("manifest", b"v1.2.3"));
+
+        assert_eq!(
+            runtime.custom_section(&component, "manifest").unwrap(),
+            Some(b"v1.2.3".to_vec())
+        );
+        assert_eq!(
+            runtime.custom_section(&component, "only-nested").unwrap(),
+            Some(b"inner".to_vec())
+        );
+        assert_eq!(runtime.custom_section(&component, "missing").unwrap(), None);
+    }
+}
+
+#[cfg(test)]
+mod poll_budget_test {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::test_support::{EmptyView, SPIN};
+    use wasmtime::component::Component;
+
+    #[tokio::test]
+    async fn it_yields_long_guest_polls() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .poll_budget(Duration::from_millis(1))
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = Component::new(&runtime.engine, SPIN).expect("failed to compile");
+        let instance = runtime
+            .linker
+            .instantiate_async(&mut runtime.store, &component)
+            .await
+            .expect("failed to instantiate component");
+        let spin = instance
+            .get_typed_func::<(u32,), (u32,)>(&mut runtime.store, "spin")
+            .expect("missing spin export");
+
+        let progressed = Arc::new(AtomicBool::new(false));
+        let task = tokio::spawn({
+            let progressed = progressed.clone();
+            async move { progressed.store(true, Ordering::SeqCst) }
+        });
+
+        let (result,) = spin
+            .call_async(&mut runtime.store, (500_000_000,))
+            .await
+            .expect("failed to invoke spin");
+        spin.post_return_async(&mut runtime.store)
+            .await
+            .expect("failed to post return");
+
+        assert_eq!(result, 500_000_000);
+        assert!(
+            progressed.load(Ordering::SeqCst),
+            "other tasks should run while the guest is spinning"
+        );
+        task.await.unwrap();
+    }
+}
+
+#[cfg(test)]
+mod shutdown_hook_test {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::test_support::EmptyView;
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    struct Socket {
+        name: &'static str,
+        log: Log,
+    }
+
+    impl Drop for Socket {
+        fn drop(&mut self) {
+            self.log.lock().unwrap().push(self.name);
+        }
+    }
+
+    #[test]
+    fn it_runs_hooks_before_dropping_the_table() {
+        let log = Log::default();
+        let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+        let view = runtime.store.data_mut();
+
+        let closed = view
+            .table
+            .push(Socket {
+                name: "closed",
+                log: log.clone(),
+            })
+            .unwrap();
+        view.table
+            .push(Socket {
+                name: "leftover",
+                log: log.clone(),
+            })
+            .unwrap();
+
+        view.on_shutdown({
+            let log = log.clone();
+            move |table| {
+                log.lock().unwrap().push("first hook");
+                table.delete(closed).unwrap();
+            }
+        });
+        view.on_shutdown({
+            let log = log.clone();
+            move |_| log.lock().unwrap().push("second hook")
+        });
+
+        drop(runtime);
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["second hook", "first hook", "closed", "leftover"]
+        );
+    }
+}
+
+#[cfg(test)]
+mod owned_resource_test {
+    use super::*;
+    use crate::test_support::EmptyView;
+
+    struct Counter(u32);
+
+    #[test]
+    fn it_deletes_the_entry_on_drop() {
+        let mut view = RuntimeView::new(EmptyView);
+
+        let resource = view.table.push(Counter(1)).unwrap();
+        let rep = resource.rep();
+
+        {
+            let mut owned = view.own_resource(resource);
+            owned.get_mut().unwrap().0 += 1;
+            assert_eq!(owned.get().unwrap().0, 2);
+        }
+
+        assert!(view.table.get(&Resource::<Counter>::new_own(rep)).is_err());
+    }
+
+    #[test]
+    fn it_keeps_released_entries() {
+        let mut view = RuntimeView::new(EmptyView);
+
+        let resource = view.table.push(Counter(7)).unwrap();
+        let resource = view.own_resource(resource).into_inner();
+
+        assert_eq!(view.table.get(&resource).unwrap().0, 7);
+    }
+}
+
+#[cfg(test)]
+mod resource_isolation_test {
+    use super::*;
+    use wasmtime::component::{ResourceType, Val};
+
+    const COUNTERS: &str = r#"
+        (component
+            (import "its:test/counters" (instance $host
+                (export "counter" (type $counter (sub resource)))
+                (export "[constructor]counter" (func (param "value" u32) (result (own $counter))))
+                (export "[method]counter.get" (func (param "self" (borrow $counter)) (result u32)))))
+            (core func $new (canon lower (func $host "[constructor]counter")))
+            (core func $get (canon lower (func $host "[method]counter.get")))
+            (core module $m
+                (import "host" "new" (func $new (param i32) (result i32)))
+                (import "host" "get" (func $get (param i32) (result i32)))
+                (func (export "make") (param i32) (result i32) (call $new (local.get 0)))
+                (func (export "read") (param i32) (result i32) (call $get (local.get 0))))
+            (core instance $h (export "new" (func $new)) (export "get" (func $get)))
+            (core instance $i (instantiate $m (with "host" (instance $h))))
+            (func (export "make") (param "value" u32) (result u32)
+                (canon lift (core func $i "make")))
+            (func (export "read") (param "handle" u32) (result u32)
+                (canon lift (core func $i "read")))
+        )
+    "#;
+
+    struct Counter(u32);
+
+    struct CounterView;
+
+    impl NestedView for CounterView {
+        fn add_all_to_linker(
+            &mut self,
+            linker: &mut Linker<RuntimeView<Self>>,
+        ) -> anyhow::Result<()> {
+            let mut counters = linker.instance("its:test/counters")?;
+            counters.resource(
+                "counter",
+                ResourceType::host::<Counter>(),
+                |mut store, rep| {
+                    store
+                        .data_mut()
+                        .table
+                        .delete(Resource::<Counter>::new_own(rep))?;
+                    Ok(())
+                },
+            )?;
+            counters.func_wrap("[constructor]counter", |mut store, (value,): (u32,)| {
+                Ok((store.data_mut().table.push(Counter(value))?,))
+            })?;
+            counters.func_wrap(
+                "[method]counter.get",
+                |store, (counter,): (Resource<Counter>,)| {
+                    Ok((store.data().table.get(&counter)?.0,))
+                },
+            )?;
+            Ok(())
+        }
+    }
+
+    async fn call(
+        runtime: &mut Runtime<CounterView>,
+        name: &str,
+        export: &str,
+        arg: u32,
+    ) -> anyhow::Result<u32> {
+        let results = runtime.call_named(name, export, &[Val::U32(arg)]).await?;
+        match results[..] {
+            [Val::U32(result)] => Ok(result),
+            _ => panic!("unexpected results {results:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_keeps_handles_private_to_their_instance() {
+        let mut runtime = runtime(false, CounterView).expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(COUNTERS)
+            .expect("failed to compile component");
+        for name in ["first", "second"] {
+            runtime
+                .instantiate_named(name, &component)
+                .await
+                .expect("failed to instantiate component");
+        }
+
+        let handle = call(&mut runtime, "first", "make", 41).await.unwrap();
+        assert_eq!(
+            call(&mut runtime, "first", "read", handle).await.unwrap(),
+            41
+        );
+        assert!(call(&mut runtime, "second", "read", handle).await.is_err());
+
+        let other = call(&mut runtime, "second", "make", 7).await.unwrap();
+        assert_eq!(
+            call(&mut runtime, "second", "read", other).await.unwrap(),
+            7
+        );
+        assert_eq!(
+            call(&mut runtime, "first", "read", handle).await.unwrap(),
+            41
+        );
+    }
+}
+
+#[cfg(test)]
+mod output_stream_test {
+    use super::*;
+    use crate::test_support::EmptyView;
+    use bytes::Bytes;
+    use tokio::io::AsyncReadExt;
+    use wasmtime_wasi::HostOutputStream;
+
+    #[tokio::test]
+    async fn it_streams_to_the_sink() {
+        let mut view = RuntimeView::new(EmptyView);
+
+        let (writer, mut reader) = tokio::io::duplex(4096);
+        let stream = view.new_output_stream(writer).unwrap();
+
+        const CHUNK: usize = 16 * 1024;
+        const TOTAL: usize = 4 * 1024 * 1024;
+
+        let consumer = tokio::spawn(async move {
+            let mut received = 0;
+            let mut buf = vec![0; CHUNK];
+            loop {
+                let n = reader.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break received;
+                }
+                assert!(buf[..n].iter().all(|b| *b == 0xAB));
+                received += n;
+            }
+        });
+
+        let output = view.table.get_mut(&stream).unwrap();
+        for _ in 0..TOTAL / CHUNK {
+            output
+                .blocking_write_and_flush(Bytes::from(vec![0xAB; CHUNK]))
+                .await
+                .unwrap();
+        }
+
+        view.table.delete(stream).unwrap();
+        assert_eq!(consumer.await.unwrap(), TOTAL);
+    }
+}
+
+#[cfg(test)]
+mod view_test {
+    use super::*;
+    use bytes::Bytes;
+    use tokio::io::AsyncReadExt;
+    use wasmtime_wasi::HostOutputStream;
+
+    struct CountingView {
+        streams_opened: usize,
+    }
+
+    impl NestedView for CountingView {
+        fn add_all_to_linker(&mut self, _: &mut Linker<RuntimeView<Self>>) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn it_exposes_the_whole_view() {
+        let mut runtime =
+            runtime(true, CountingView { streams_opened: 0 }).expect("Failed to build runtime");
+        let (writer, mut reader) = tokio::io::duplex(64);
+
+        let view = runtime.view_mut();
+        let stream = view.new_output_stream(writer).unwrap();
+        view.nested_view.streams_opened += 1;
+        view.table
+            .get_mut(&stream)
+            .unwrap()
+            .blocking_write_and_flush(Bytes::from_static(b"hello"))
+            .await
+            .unwrap();
+
+        let view = runtime.view();
+        assert_eq!(view.nested_view.streams_opened, 1);
+        assert!(view.table.get(&stream).is_ok());
+
+        let mut written = [0; 5];
+        reader.read_exact(&mut written).await.unwrap();
+        assert_eq!(&written, b"hello");
+    }
+}
+
+#[cfg(test)]
+mod resource_table_capacity_test {
+    use super::*;
+    use crate::test_support::EmptyView;
+
+    #[test]
+    fn it_grows_past_the_capacity_hint() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .resource_table_capacity(4)
+            .build()
+            .expect("Failed to build runtime");
+
+        let table = &mut runtime.store.data_mut().table;
+        let resources: Vec<_> = (0..100u32).map(|i| table.push(i).unwrap()).collect();
+
+        for (i, resource) in resources.iter().enumerate() {
+            assert_eq!(*table.get(resource).unwrap(), i as u32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod linker_timings_test {
+    use super::*;
+    use crate::test_support::EmptyView;
+
+    #[test]
+    fn it_records_linker_build_time() {
+        let runtime = runtime(true, EmptyView).expect("Failed to build runtime");
+
+        let timings = runtime.linker_timings();
+        assert!(timings.wasi > Duration::ZERO);
+        assert!(timings.total >= timings.wasi + timings.nested_view);
+        assert_eq!(runtime.linker_build_time(), timings.total);
+    }
+}
+
+#[cfg(test)]
+mod capability_test {
+    use super::*;
+    use crate::test_support::EmptyView;
+
+    struct ApiKey(&'static str);
+    struct Unrelated;
+
+    #[test]
+    fn it_only_accepts_granted_capabilities() {
+        let mut view = RuntimeView::new(EmptyView);
+
+        let key = view.grant_capability(ApiKey("secret")).unwrap();
+        assert_eq!(view.capability(&key).unwrap().0, "secret");
+
+        let forged = Resource::<Capability<ApiKey>>::new_own(key.rep() + 100);
+        assert!(view.capability(&forged).is_err());
+
+        let unrelated = view.table.push(Unrelated).unwrap();
+        let mistyped = Resource::<Capability<ApiKey>>::new_own(unrelated.rep());
+        assert!(view.capability(&mistyped).is_err());
+
+        let borrowed = Resource::<Capability<ApiKey>>::new_borrow(key.rep());
+        let revoked = view.revoke_capability(key).unwrap();
+        assert_eq!(revoked.0, "secret");
+        assert!(view.capability(&borrowed).is_err());
+    }
+}
+
+#[cfg(test)]
+mod shared_engine_test {
+    use super::*;
+    use crate::test_support::{answer, EmptyView};
+
+    #[tokio::test]
+    async fn it_shares_compiled_components_between_runtimes() {
+        let engine = Engine::new(&default_config()).expect("failed to create engine");
+        let component = wasmtime::component::Component::new(&engine, answer(42))
+            .expect("failed to compile component");
+
+        for _ in 0..2 {
+            let mut runtime =
+                runtime_with_engine(&engine, false, EmptyView).expect("Failed to build runtime");
+            runtime
+                .instantiate_named("plugin", &component)
+                .await
+                .expect("failed to instantiate component");
+            let results = runtime.call_named("plugin", "answer", &[]).await.unwrap();
+            assert_eq!(results, vec![wasmtime::component::Val::U32(42)]);
+        }
+
+        assert!(RuntimeBuilder::new()
+            .engine(engine)
+            .nested_view(EmptyView)
+            .max_wasm_stack(1024 * 1024)
+            .build()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn it_runs_guests_on_engines_with_fuel_and_epochs() {
+        let mut config = default_config();
+        config.consume_fuel(true).epoch_interruption(true);
+        let engine = Engine::new(&config).expect("failed to create engine");
+
+        let mut runtime =
+            runtime_with_engine(&engine, false, EmptyView).expect("Failed to build runtime");
+        let component = runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+        engine.increment_epoch();
+        let results = runtime.call_named("plugin", "answer", &[]).await.unwrap();
+        assert_eq!(results, vec![wasmtime::component::Val::U32(42)]);
+    }
+
+    #[test]
+    fn it_rejects_engines_without_the_component_model() {
+        let mut config = default_config();
+        config.wasm_component_model(false);
+        let engine = Engine::new(&config).expect("failed to create engine");
+
+        let err = runtime_with_engine(&engine, false, EmptyView)
+            .err()
+            .expect("an engine without the component model should be rejected");
+        assert!(matches!(err, RuntimeError::EngineCreation(_)));
+    }
 }
@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Context};
+use wasmtime::component::{types::ComponentItem, Component};
+
+use crate::{NestedView, Runtime, RuntimeError};
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Makes the functions of `interface`, as exported by the instance registered as `name`,
+    /// available to components instantiated afterwards, so one component's export satisfies
+    /// another's import without composing them ahead of time.
+    ///
+    /// `component` is the component `name` was instantiated from, which is where the interface's
+    /// functions are listed. Calls are forwarded to the exporting instance in this runtime's
+    /// store, so the linked interface can't be used by [`Runtime::spawn_instance`]. Only
+    /// functions are forwarded; interfaces exporting resources can't be linked this way.
+    pub fn link_exports(
+        &mut self,
+        name: &str,
+        component: &Component,
+        interface: &str,
+    ) -> Result<(), RuntimeError> {
+        let instance = self
+            .named_instance(name)
+            .ok_or_else(|| anyhow!("no instance named `{name}`"))?;
+
+        let Some((_, ComponentItem::ComponentInstance(ty))) = component
+            .component_type()
+            .exports(&self.engine)
+            .find(|(export, _)| *export == interface)
+        else {
+            return Err(anyhow!("instance `{name}` doesn't export `{interface}`").into());
+        };
+        let functions: Vec<String> = ty
+            .exports(&self.engine)
+            .filter(|(_, item)| matches!(item, ComponentItem::ComponentFunc(_)))
+            .map(|(function, _)| function.to_string())
+            .collect();
+
+        let mut exports = instance.exports(&mut self.store);
+        let mut exported = exports
+            .instance(interface)
+            .ok_or_else(|| anyhow!("instance `{name}` doesn't export `{interface}`"))?;
+        let funcs = functions
+            .iter()
+            .map(|function| {
+                exported
+                    .func(function)
+                    .map(|func| (function, func))
+                    .ok_or_else(|| anyhow!("`{interface}` has no function `{function}`"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut linker = self
+            .linker
+            .instance(interface)
+            .map_err(RuntimeError::LinkerSetup)?;
+        for (function, func) in funcs {
+            linker
+                .func_new_async(function, move |mut store, params, results| {
+                    Box::new(async move {
+                        func.call_async(&mut store, params, results).await?;
+                        func.post_return_async(&mut store).await
+                    })
+                })
+                .with_context(|| format!("failed to link `{interface}#{function}`"))
+                .map_err(RuntimeError::LinkerSetup)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod link_exports_test {
+    use wasmtime::component::Val;
+
+    use crate::{runtime, test_support::EmptyView};
+
+    const EXPORTS_MATH: &str = r#"
+        (component
+            (core module $m
+                (func (export "double") (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2))))
+            (core instance $i (instantiate $m))
+            (func $double (param "x" u32) (result u32) (canon lift (core func $i "double")))
+            (instance $math (export "double" (func $double)))
+            (export "its:test/math" (instance $math))
+        )
+    "#;
+
+    const IMPORTS_MATH: &str = r#"
+        (component
+            (import "its:test/math" (instance $math
+                (export "double" (func (param "x" u32) (result u32)))))
+            (core func $double (canon lower (func $math "double")))
+            (core module $m
+                (import "host" "double" (func $double (param i32) (result i32)))
+                (func (export "run") (result i32) (call $double (i32.const 21))))
+            (core instance $i (instantiate $m
+                (with "host" (instance (export "double" (func $double))))))
+            (func (export "run") (result u32) (canon lift (core func $i "run")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_links_one_components_exports_to_anothers_imports() {
+        let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let provider = runtime
+            .load_component(EXPORTS_MATH)
+            .expect("failed to compile provider");
+        let consumer = runtime
+            .load_component(IMPORTS_MATH)
+            .expect("failed to compile consumer");
+
+        runtime
+            .instantiate_named("math", &provider)
+            .await
+            .expect("failed to instantiate provider");
+        assert!(runtime.validate(&consumer).is_err());
+
+        runtime
+            .link_exports("math", &provider, "its:test/math")
+            .expect("failed to link exports");
+        runtime
+            .instantiate_named("consumer", &consumer)
+            .await
+            .expect("failed to instantiate consumer");
+
+        let results = runtime
+            .call_named("consumer", "run", &[])
+            .await
+            .expect("failed to call run");
+        assert_eq!(results, [Val::U32(42)]);
+    }
+}
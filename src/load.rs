@@ -0,0 +1,501 @@
+use std::{fs, future::Future, num::NonZeroUsize, path::Path, sync::Arc, thread};
+
+use anyhow::{anyhow, Context};
+use rayon::ThreadPool;
+use tokio::sync::Semaphore;
+use wasmtime::{
+    component::{types, Component, InstancePre},
+    Engine, Precompiled,
+};
+
+use crate::{
+    footprint, trace::traced, FootprintEstimate, NestedView, Runtime, RuntimeError, RuntimeEvent,
+    RuntimeView,
+};
+
+type ValidationPolicyFn = dyn Fn(&Engine, &types::Component, Option<&FootprintEstimate>) -> Result<(), String>
+    + Send
+    + Sync;
+
+pub(crate) type ValidationPolicy = Arc<ValidationPolicyFn>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArtifactInfo {
+    pub kind: Precompiled,
+    /// Whether the artifact holds a component rather than a core module. This says nothing about
+    /// whether it was compiled by a compatible engine, see [`Runtime::check_artifact`].
+    pub is_component_artifact: bool,
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Cheaply checks that `bytes` look like an artifact produced by `Engine::precompile_*`,
+    /// without deserializing it.
+    ///
+    /// This only inspects the artifact's header and doesn't compare the engine version or
+    /// configuration it was compiled with against this runtime's engine. Those are verified when
+    /// the artifact is deserialized by [`Runtime::load_precompiled`], which rejects mismatches
+    /// with an error; the point of this check is to never hand arbitrary bytes to the unsafe
+    /// deserializer.
+    pub fn check_artifact(&self, bytes: &[u8]) -> anyhow::Result<ArtifactInfo> {
+        let kind = self
+            .engine
+            .detect_precompiled(bytes)
+            .ok_or_else(|| anyhow!("bytes are not a precompiled wasmtime artifact"))?;
+
+        Ok(ArtifactInfo {
+            kind,
+            is_component_artifact: kind == Precompiled::Component,
+        })
+    }
+
+    /// Compiles a component from binary or text format using this runtime's engine.
+    ///
+    /// If a validation policy was configured it is consulted after compilation, and a
+    /// component it rejects is returned as [`RuntimeError::Rejected`].
+    pub fn load_component(&self, bytes: impl AsRef<[u8]>) -> Result<Component, RuntimeError> {
+        let bytes = bytes.as_ref();
+        let pool = self.compilation_pool.as_deref();
+        let policy = self.validation_policy.as_deref();
+        let component = traced!("load_component", bytes = bytes.len(); {
+            compile(&self.engine, pool, policy, bytes)
+        })?;
+        self.events.publish(RuntimeEvent::Compiled);
+        Ok(component)
+    }
+
+    /// Reads and compiles a component from a file in binary or text format, see
+    /// [`Runtime::load_component`].
+    pub fn load_component_file(&self, path: impl AsRef<Path>) -> Result<Component, RuntimeError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .with_context(|| format!("failed to read component from {}", path.display()))?;
+        self.load_component(bytes)
+    }
+
+    /// Compiles a component ahead of time into an artifact that [`Runtime::load_precompiled`]
+    /// loads without compiling again, e.g. to cache it on disk between runs.
+    pub fn precompile_component(&self, bytes: impl AsRef<[u8]>) -> anyhow::Result<Vec<u8>> {
+        self.engine.precompile_component(bytes.as_ref())
+    }
+
+    /// Loads an artifact produced by [`Runtime::precompile_component`], consulting the
+    /// validation policy like [`Runtime::load_component`].
+    ///
+    /// # Safety
+    ///
+    /// The artifact is trusted to contain valid machine code. `bytes` must have been produced
+    /// by `precompile_component` (or `Engine::precompile_component`) and not tampered with
+    /// since. Wasmtime rejects artifacts from a different version or an incompatible engine
+    /// configuration, but can't detect modified code; [`Runtime::check_artifact`] only looks at
+    /// the header.
+    pub unsafe fn load_precompiled(&self, bytes: &[u8]) -> Result<Component, RuntimeError> {
+        let component = Component::deserialize(&self.engine, bytes)?;
+
+        if let Some(policy) = &self.validation_policy {
+            policy(&self.engine, &component.component_type(), None)
+                .map_err(RuntimeError::Rejected)?;
+        }
+
+        Ok(component)
+    }
+
+    /// Compiles a component on a blocking task, giving up as soon as `cancelled` resolves.
+    ///
+    /// Wasmtime can't interrupt a compilation that is already running, so cancellation is best
+    /// effort: the caller gets [`RuntimeError::Cancelled`] right away and the background
+    /// compilation runs to completion on its own, after which `bytes` and its result are dropped.
+    pub async fn load_component_cancellable(
+        &self,
+        bytes: impl AsRef<[u8]> + Send + 'static,
+        cancelled: impl Future<Output = ()>,
+    ) -> Result<Component, RuntimeError> {
+        let engine = self.engine.clone();
+        let pool = self.compilation_pool.clone();
+        let policy = self.validation_policy.clone();
+        let compilation = tokio::task::spawn_blocking(move || {
+            compile(&engine, pool.as_deref(), policy.as_deref(), bytes.as_ref())
+        });
+
+        let component = tokio::select! {
+            biased;
+            _ = cancelled => Err(RuntimeError::Cancelled),
+            result = compilation => result.map_err(anyhow::Error::from)?,
+        }?;
+        self.events.publish(RuntimeEvent::Compiled);
+        Ok(component)
+    }
+
+    /// Compiles and pre-instantiates many components at once, failing on the first error.
+    ///
+    /// See [`Runtime::warm_each`] for the concurrency behavior.
+    pub async fn warm_many(
+        &self,
+        components: &[&[u8]],
+    ) -> Result<Vec<InstancePre<RuntimeView<T>>>, RuntimeError> {
+        self.warm_each(components).await.into_iter().collect()
+    }
+
+    /// Compiles and pre-instantiates many components, reporting a result for each one.
+    ///
+    /// Compilation runs on blocking tasks, at most `warm_concurrency` at a time (the available
+    /// parallelism unless configured on the builder), so the calling task isn't blocked while
+    /// the batch compiles. Results are returned in the order of `components`.
+    pub async fn warm_each(
+        &self,
+        components: &[&[u8]],
+    ) -> Vec<Result<InstancePre<RuntimeView<T>>, RuntimeError>> {
+        let concurrency = self
+            .warm_concurrency
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get);
+        let permits = Arc::new(Semaphore::new(concurrency));
+
+        let mut compilations = Vec::with_capacity(components.len());
+        for bytes in components {
+            let permit = permits
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("the semaphore is never closed");
+            let engine = self.engine.clone();
+            let pool = self.compilation_pool.clone();
+            let policy = self.validation_policy.clone();
+            let bytes = bytes.to_vec();
+            compilations.push(tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                compile(&engine, pool.as_deref(), policy.as_deref(), &bytes)
+            }));
+        }
+
+        let mut warmed = Vec::with_capacity(compilations.len());
+        for compilation in compilations {
+            let component = match compilation.await {
+                Ok(component) => component,
+                Err(err) => Err(anyhow::Error::from(err).into()),
+            };
+            warmed.push(component.and_then(|component| {
+                self.events.publish(RuntimeEvent::Compiled);
+                Ok(self.linker.instantiate_pre(&component)?)
+            }));
+        }
+        warmed
+    }
+}
+
+/// Compiles `bytes`, running Cranelift's parallel work on `pool` when one is configured.
+pub(crate) fn compile(
+    engine: &Engine,
+    pool: Option<&ThreadPool>,
+    policy: Option<&ValidationPolicyFn>,
+    bytes: &[u8],
+) -> Result<Component, RuntimeError> {
+    let component = match pool {
+        Some(pool) => pool.install(|| Component::new(engine, bytes))?,
+        None => Component::new(engine, bytes)?,
+    };
+
+    if let Some(policy) = policy {
+        let footprint = footprint::measure(bytes, &component)?;
+        policy(engine, &component.component_type(), Some(&footprint))
+            .map_err(RuntimeError::Rejected)?;
+    }
+
+    Ok(component)
+}
+
+#[cfg(test)]
+mod validation_policy_test {
+    use crate::{test_support::EmptyView, RuntimeBuilder, RuntimeError};
+
+    const NO_IMPORTS: &str = "(component)";
+    const IMPORTS_HOST: &str = r#"
+        (component
+            (import "host" (func (result u32)))
+        )
+    "#;
+
+    #[test]
+    fn it_rejects_components_failing_the_policy() {
+        let runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .validation_policy(
+                |engine, component, _| match component.imports(engine).next() {
+                    Some((name, _)) => Err(format!("import `{name}` is not allowed")),
+                    None => Ok(()),
+                },
+            )
+            .build()
+            .expect("Failed to build runtime");
+
+        runtime
+            .load_component(NO_IMPORTS)
+            .expect("component without imports should be accepted");
+
+        match runtime.load_component(IMPORTS_HOST) {
+            Err(RuntimeError::Rejected(reason)) => {
+                assert_eq!(reason, "import `host` is not allowed")
+            }
+            Err(err) => panic!("expected a rejection, got {err}"),
+            Ok(_) => panic!("expected a rejection"),
+        }
+    }
+
+    #[test]
+    fn it_rejects_components_declaring_too_much_memory() {
+        const LIMIT: u64 = 4 * 64 * 1024;
+
+        let runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .validation_policy(|_, _, footprint| match footprint {
+                Some(footprint) if footprint.linear_memory > LIMIT => Err(format!(
+                    "declares {} bytes of memory",
+                    footprint.linear_memory
+                )),
+                _ => Ok(()),
+            })
+            .build()
+            .expect("Failed to build runtime");
+
+        runtime
+            .load_component("(component (core module (memory 4)))")
+            .expect("component within the limit should be accepted");
+
+        match runtime.load_component("(component (core module (memory 5)))") {
+            Err(RuntimeError::Rejected(reason)) => {
+                assert_eq!(
+                    reason,
+                    format!("declares {} bytes of memory", 5 * 64 * 1024)
+                )
+            }
+            Err(err) => panic!("expected a rejection, got {err}"),
+            Ok(_) => panic!("expected a rejection"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod warm_test {
+    use crate::{
+        test_support::{answer, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_warms_components_concurrently() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .warm_concurrency(2)
+            .build()
+            .expect("Failed to build runtime");
+
+        let sources: Vec<_> = (0..5).map(answer).collect();
+        let components: Vec<_> = sources.iter().map(|s| s.as_bytes()).collect();
+
+        let pres = runtime
+            .warm_many(&components)
+            .await
+            .expect("failed to warm components");
+        assert_eq!(pres.len(), 5);
+
+        for (expected, pre) in pres.iter().enumerate() {
+            let instance = pre
+                .instantiate_async(&mut runtime.store)
+                .await
+                .expect("failed to instantiate component");
+            let func = instance
+                .get_typed_func::<(), (u32,)>(&mut runtime.store, "answer")
+                .expect("missing answer export");
+            let (result,) = func
+                .call_async(&mut runtime.store, ())
+                .await
+                .expect("failed to invoke answer");
+            func.post_return_async(&mut runtime.store)
+                .await
+                .expect("failed to post return");
+            assert_eq!(result, expected as u32);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reports_errors_per_component() {
+        let runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .build()
+            .expect("Failed to build runtime");
+
+        let valid = answer(1);
+        let results = runtime
+            .warm_each(&[valid.as_bytes(), b"not a component", valid.as_bytes()])
+            .await;
+
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+        assert!(runtime
+            .warm_many(&[valid.as_bytes(), b"not a component"])
+            .await
+            .is_err());
+    }
+}
+
+#[cfg(test)]
+mod cancellable_load_test {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{runtime, test_support::EmptyView, RuntimeError};
+
+    /// Component source that keeps `_alive` referenced until the compilation drops it.
+    struct Tracked {
+        bytes: Vec<u8>,
+        _alive: Arc<()>,
+    }
+
+    impl AsRef<[u8]> for Tracked {
+        fn as_ref(&self) -> &[u8] {
+            &self.bytes
+        }
+    }
+
+    #[tokio::test]
+    async fn it_cancels_compilation() {
+        let runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let alive = Arc::new(());
+        let source = Tracked {
+            bytes: b"(component)".to_vec(),
+            _alive: alive.clone(),
+        };
+        let result = runtime
+            .load_component_cancellable(source, std::future::ready(()))
+            .await;
+        assert!(matches!(result, Err(RuntimeError::Cancelled)));
+
+        let released = async {
+            while Arc::strong_count(&alive) > 1 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), released)
+            .await
+            .expect("the background compilation should finish and drop its input");
+
+        runtime
+            .load_component_cancellable(b"(component)".to_vec(), std::future::pending())
+            .await
+            .expect("uncancelled compilation should succeed");
+    }
+}
+
+#[cfg(test)]
+mod artifact_test {
+    use wasmtime::Precompiled;
+
+    use crate::{runtime, test_support::EmptyView, RuntimeBuilder};
+
+    const EMPTY_COMPONENT: &[u8] = b"\0asm\x0d\0\x01\0";
+    const EMPTY_MODULE: &[u8] = b"\0asm\x01\0\0\0";
+
+    #[test]
+    fn it_checks_artifacts() {
+        let runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let component = runtime
+            .engine
+            .precompile_component(EMPTY_COMPONENT)
+            .expect("failed to precompile component");
+        let info = runtime.check_artifact(&component).unwrap();
+        assert_eq!(info.kind, Precompiled::Component);
+        assert!(info.is_component_artifact);
+
+        let module = runtime
+            .engine
+            .precompile_module(EMPTY_MODULE)
+            .expect("failed to precompile module");
+        let info = runtime.check_artifact(&module).unwrap();
+        assert_eq!(info.kind, Precompiled::Module);
+        assert!(!info.is_component_artifact);
+
+        assert!(runtime.check_artifact(EMPTY_COMPONENT).is_err());
+        assert!(runtime.check_artifact(b"garbage").is_err());
+    }
+
+    #[test]
+    fn it_leaves_engine_mismatches_to_deserialization() {
+        let runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+        let fueled = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .with_fuel(1_000)
+            .build()
+            .expect("Failed to build runtime");
+
+        let artifact = fueled
+            .precompile_component(EMPTY_COMPONENT)
+            .expect("failed to precompile component");
+        let info = runtime.check_artifact(&artifact).unwrap();
+        assert!(info.is_component_artifact);
+
+        // SAFETY: the artifact was produced by wasmtime and not modified, only by an engine with
+        // a different configuration, which deserialization detects.
+        assert!(unsafe { runtime.load_precompiled(&artifact) }.is_err());
+    }
+}
+
+#[cfg(test)]
+mod load_file_test {
+    use std::fs;
+
+    use crate::{runtime, test_support::EmptyView};
+
+    #[test]
+    fn it_loads_components_from_files() {
+        let runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let path = std::env::temp_dir().join(format!("its-wasmtime-{}.wat", std::process::id()));
+        fs::write(&path, "(component)").expect("failed to write component");
+        let loaded = runtime.load_component_file(&path);
+        fs::remove_file(&path).expect("failed to clean up component");
+        loaded.expect("failed to load component");
+
+        let err = runtime
+            .load_component_file("./does/not/exist.wasm")
+            .err()
+            .expect("missing files should fail to load");
+        assert!(err.to_string().contains("does/not/exist.wasm"));
+    }
+}
+
+#[cfg(test)]
+mod precompile_test {
+    use crate::{
+        runtime,
+        test_support::{answer, EmptyView},
+    };
+
+    #[tokio::test]
+    async fn it_loads_precompiled_components() {
+        let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let artifact = runtime
+            .precompile_component(answer(42))
+            .expect("failed to precompile component");
+        assert!(
+            runtime
+                .check_artifact(&artifact)
+                .unwrap()
+                .is_component_artifact
+        );
+
+        // SAFETY: the artifact was just produced by this runtime's engine.
+        let component =
+            unsafe { runtime.load_precompiled(&artifact) }.expect("failed to load artifact");
+        runtime
+            .instantiate_named("plugin", &component)
+            .await
+            .expect("failed to instantiate component");
+        let results = runtime.call_named("plugin", "answer", &[]).await.unwrap();
+        assert_eq!(results, vec![wasmtime::component::Val::U32(42)]);
+    }
+}
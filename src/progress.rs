@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+
+use wasmtime::component::Linker;
+
+/// Interface guests import to report progress, with a single function
+/// `report: func(fraction: f32, message: string)`.
+pub const PROGRESS_INTERFACE: &str = "its:runtime/progress";
+
+pub(crate) type ProgressCallback = Box<dyn FnMut(f32, &str) + Send>;
+
+pub(crate) fn add_progress_to_linker<T>(
+    linker: &mut Linker<T>,
+    callback: ProgressCallback,
+) -> anyhow::Result<()> {
+    let callback = Arc::new(Mutex::new(callback));
+
+    linker.instance(PROGRESS_INTERFACE)?.func_wrap(
+        "report",
+        move |_, (fraction, message): (f32, String)| {
+            let mut callback = callback.lock().unwrap();
+            callback(clamp_fraction(fraction), &message);
+            Ok(())
+        },
+    )
+}
+
+/// Clamps guest-reported fractions to `[0, 1]`, treating NaN as no progress.
+fn clamp_fraction(fraction: f32) -> f32 {
+    if fraction.is_nan() {
+        0.0
+    } else {
+        fraction.clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod progress_test {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{test_support::EmptyView, RuntimeBuilder};
+
+    const REPORTS_PROGRESS: &str = r#"
+        (component
+            (import "its:runtime/progress" (instance $p
+                (export "report" (func (param "fraction" f32) (param "message" string)))))
+            (core module $memory
+                (memory (export "memory") 1)
+                (data (i32.const 0) "startmidenddone"))
+            (core instance $memory (instantiate $memory))
+            (alias core export $memory "memory" (core memory $mem))
+            (core func $report (canon lower (func $p "report") (memory $mem)))
+            (core module $m
+                (import "host" "report" (func $report (param f32 i32 i32)))
+                (func (export "run")
+                    (call $report (f32.const -0.5) (i32.const 0) (i32.const 5))
+                    (call $report (f32.const 0.5) (i32.const 5) (i32.const 3))
+                    (call $report (f32.const 1.5) (i32.const 8) (i32.const 3))
+                    (call $report (f32.const 1) (i32.const 11) (i32.const 4))))
+            (core instance $host (export "report" (func $report)))
+            (core instance $i (instantiate $m (with "host" (instance $host))))
+            (func (export "run") (canon lift (core func $i "run")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_reports_clamped_progress() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .on_progress({
+                let reports = reports.clone();
+                move |fraction, message| {
+                    reports
+                        .lock()
+                        .unwrap()
+                        .push((fraction, message.to_string()))
+                }
+            })
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(REPORTS_PROGRESS)
+            .expect("failed to compile component");
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let run = instance
+            .get_typed_func::<(), ()>(&mut runtime.store, "run")
+            .expect("missing run export");
+
+        run.call_async(&mut runtime.store, ())
+            .await
+            .expect("failed to invoke run");
+        run.post_return_async(&mut runtime.store)
+            .await
+            .expect("failed to post return");
+
+        assert_eq!(
+            *reports.lock().unwrap(),
+            vec![
+                (0.0, "start".to_string()),
+                (0.5, "mid".to_string()),
+                (1.0, "end".to_string()),
+                (1.0, "done".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+use wasmtime::component::Component;
+
+use crate::{NestedView, Runtime, RuntimeError};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct QuarantinePolicy {
+    pub(crate) threshold: u32,
+    pub(crate) window: Duration,
+    pub(crate) cooldown: Duration,
+}
+
+#[derive(Default)]
+struct TrapRecord {
+    /// When each trap within the policy's window happened, oldest first.
+    traps: VecDeque<Instant>,
+    quarantined_until: Option<Instant>,
+}
+
+/// Counts guest traps per component and quarantines components that trap too often.
+pub(crate) struct QuarantineTracker {
+    policy: QuarantinePolicy,
+    records: HashMap<u64, TrapRecord>,
+    /// Component key of every named instance, so failed calls can be attributed.
+    instances: HashMap<String, u64>,
+    /// Keys of the components seen so far, by the address of their compiled code, so each is
+    /// only hashed once. The component is kept alive so the address can't be reused.
+    keys: HashMap<usize, (Component, u64)>,
+}
+
+impl QuarantineTracker {
+    pub(crate) fn new(policy: QuarantinePolicy) -> Self {
+        Self {
+            policy,
+            records: HashMap::new(),
+            instances: HashMap::new(),
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Identifies a component by a hash of its compiled artifact, so reloading the same
+    /// component gets the same key.
+    fn component_key(&mut self, component: &Component) -> anyhow::Result<u64> {
+        let address = component.image_range().start as usize;
+        if let Some((_, key)) = self.keys.get(&address) {
+            return Ok(*key);
+        }
+
+        let mut hasher = DefaultHasher::new();
+        component.serialize()?.hash(&mut hasher);
+        let key = hasher.finish();
+        self.keys.insert(address, (component.clone(), key));
+        Ok(key)
+    }
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Fails with [`RuntimeError::Quarantined`] if `component` is quarantined, and otherwise
+    /// returns its key when a quarantine policy is configured.
+    pub(crate) fn check_quarantine(
+        &mut self,
+        component: &Component,
+    ) -> anyhow::Result<Option<u64>> {
+        let Some(tracker) = &mut self.quarantine else {
+            return Ok(None);
+        };
+
+        let key = tracker.component_key(component)?;
+        if let Some(record) = tracker.records.get_mut(&key) {
+            match record.quarantined_until {
+                Some(until) if until > Instant::now() => {
+                    return Err(RuntimeError::Quarantined {
+                        remaining: until - Instant::now(),
+                    }
+                    .into());
+                }
+                Some(_) => record.quarantined_until = None,
+                None => {}
+            }
+        }
+
+        Ok(Some(key))
+    }
+
+    pub(crate) fn track_instance(&mut self, name: &str, key: Option<u64>) {
+        if let (Some(tracker), Some(key)) = (&mut self.quarantine, key) {
+            tracker.instances.insert(name.into(), key);
+        }
+    }
+
+    /// Counts a guest trap in the instance registered as `name` against its component.
+    pub(crate) fn record_trap(&mut self, name: &str) {
+        let Some(tracker) = &mut self.quarantine else {
+            return;
+        };
+        let Some(key) = tracker.instances.get(name) else {
+            return;
+        };
+
+        let now = Instant::now();
+        let record = tracker.records.entry(*key).or_default();
+        while record
+            .traps
+            .front()
+            .is_some_and(|trapped_at| now - *trapped_at > tracker.policy.window)
+        {
+            record.traps.pop_front();
+        }
+        record.traps.push_back(now);
+
+        if record.traps.len() >= tracker.policy.threshold as usize {
+            record.traps.clear();
+            record.quarantined_until = Some(now + tracker.policy.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod quarantine_test {
+    use std::time::Duration;
+
+    use wasmtime::component::Val;
+
+    use crate::{
+        test_support::{answer, EmptyView},
+        RuntimeBuilder, RuntimeError,
+    };
+
+    const TRAPS: &str = r#"
+        (component
+            (core module $m (func (export "trap") (result i32) unreachable))
+            (core instance $i (instantiate $m))
+            (func (export "trap") (result u32) (canon lift (core func $i "trap")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_quarantines_trapping_components() {
+        let cooldown = Duration::from_millis(200);
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .quarantine_policy(2, Duration::from_secs(60), cooldown)
+            .build()
+            .expect("Failed to build runtime");
+
+        let traps = runtime
+            .load_component(TRAPS)
+            .expect("failed to compile component");
+        let healthy = runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+
+        runtime
+            .instantiate_named("first", &traps)
+            .await
+            .expect("failed to instantiate component");
+        for _ in 0..2 {
+            assert!(runtime.call_named("first", "trap", &[]).await.is_err());
+        }
+
+        let err = runtime
+            .instantiate_named("second", &traps)
+            .await
+            .expect_err("component should be quarantined");
+        assert!(matches!(err, RuntimeError::Quarantined { .. }));
+        runtime
+            .instantiate_named("healthy", &healthy)
+            .await
+            .expect("other components are not affected");
+
+        tokio::time::sleep(cooldown).await;
+        runtime
+            .instantiate_named("second", &traps)
+            .await
+            .expect("quarantine should lift after the cooldown");
+    }
+
+    #[tokio::test]
+    async fn it_forgets_traps_outside_the_window() {
+        let window = Duration::from_millis(50);
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .quarantine_policy(2, window, Duration::from_secs(60))
+            .build()
+            .expect("Failed to build runtime");
+
+        let traps = runtime
+            .load_component(TRAPS)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("first", &traps)
+            .await
+            .expect("failed to instantiate component");
+
+        assert!(runtime.call_named("first", "trap", &[]).await.is_err());
+        tokio::time::sleep(window * 2).await;
+        assert!(runtime.call_named("first", "trap", &[]).await.is_err());
+
+        runtime
+            .instantiate_named("second", &traps)
+            .await
+            .expect("traps outside the window should not count");
+    }
+
+    #[tokio::test]
+    async fn it_ignores_failures_that_are_not_traps() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .quarantine_policy(1, Duration::from_secs(60), Duration::from_secs(60))
+            .build()
+            .expect("Failed to build runtime");
+
+        let healthy = runtime
+            .load_component(answer(42))
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("first", &healthy)
+            .await
+            .expect("failed to instantiate component");
+
+        assert!(runtime.call_named("first", "missing", &[]).await.is_err());
+        assert!(runtime
+            .call_named("first", "answer", &[Val::U32(1)])
+            .await
+            .is_err());
+
+        runtime
+            .instantiate_named("second", &healthy)
+            .await
+            .expect("host-side call errors should not quarantine the component");
+    }
+}
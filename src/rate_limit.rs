@@ -0,0 +1,195 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+
+use crate::{global::DynHostFn, RuntimeError};
+
+/// Allows `calls` calls per `per`, refilled continuously, with bursts of up to `calls`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub calls: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    pub fn new(calls: u32, per: Duration) -> Self {
+        Self { calls, per }
+    }
+}
+
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.calls as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn try_take(&mut self) -> bool {
+        let capacity = self.limit.calls as f64;
+        let elapsed = self.refilled_at.elapsed().as_secs_f64();
+        let per = self.limit.per.as_secs_f64();
+        if per > 0.0 {
+            self.tokens = (self.tokens + elapsed / per * capacity).min(capacity);
+        }
+        self.refilled_at = Instant::now();
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Rate limits configured on the builder, keyed by `interface#name`.
+#[derive(Default)]
+pub(crate) struct RateLimits(HashMap<String, RateLimit>);
+
+impl RateLimits {
+    pub(crate) fn insert(&mut self, path: String, limit: RateLimit) {
+        self.0.insert(path, limit);
+    }
+
+    /// Wraps `func` in a fresh token bucket if a limit is configured for `path`.
+    ///
+    /// Calls past the limit fail with [`RuntimeError::RateLimited`], which traps the guest.
+    pub(crate) fn apply(&self, path: String, func: DynHostFn) -> DynHostFn {
+        let Some(limit) = self.0.get(&path) else {
+            return func;
+        };
+
+        let bucket = Mutex::new(TokenBucket::new(*limit));
+        Arc::new(move |params, results| {
+            if !bucket.lock().unwrap().try_take() {
+                return Err(RuntimeError::RateLimited {
+                    function: path.clone(),
+                }
+                .into());
+            }
+            func(params, results)
+        })
+    }
+
+    /// Fails if a limit is configured for a function that isn't among the `linked` global host
+    /// functions and import shims, as nothing else goes through [`RateLimits::apply`].
+    pub(crate) fn ensure_applied<'a>(
+        &self,
+        linked: impl IntoIterator<Item = &'a String>,
+    ) -> anyhow::Result<()> {
+        let linked: Vec<_> = linked.into_iter().collect();
+        let mut unlimited: Vec<_> = self
+            .0
+            .keys()
+            .filter(|path| !linked.contains(path))
+            .map(String::as_str)
+            .collect();
+        if unlimited.is_empty() {
+            return Ok(());
+        }
+
+        unlimited.sort_unstable();
+        bail!(
+            "can't rate limit {}, only global host functions and import shims can be limited",
+            unlimited.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod rate_limit_test {
+    use std::time::Duration;
+
+    use wasmtime::component::Val;
+
+    use crate::{test_support::EmptyView, ImportShim, RateLimit, RuntimeBuilder, RuntimeError};
+
+    const CALLS_EXPENSIVE: &str = r#"
+        (component
+            (import "its:test/crypto" (instance $c
+                (export "hash" (func (result u32)))))
+            (core func $hash (canon lower (func $c "hash")))
+            (core module $m
+                (import "host" "hash" (func $hash (result i32)))
+                (func (export "run") (result i32) (call $hash)))
+            (core instance $host (export "hash" (func $hash)))
+            (core instance $i (instantiate $m (with "host" (instance $host))))
+            (func (export "run") (result u32) (canon lift (core func $i "run")))
+        )
+    "#;
+
+    #[tokio::test]
+    async fn it_traps_calls_past_the_limit() {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .shim_missing_imports([ImportShim::returning(
+                "its:test/crypto",
+                "hash",
+                vec![Val::U32(7)],
+            )])
+            .rate_limit_host_fn(
+                "its:test/crypto",
+                "hash",
+                RateLimit::new(3, Duration::from_secs(3600)),
+            )
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(CALLS_EXPENSIVE)
+            .expect("failed to compile component");
+
+        for call in 0..4 {
+            let name = format!("plugin-{call}");
+            runtime
+                .instantiate_named(&name, &component)
+                .await
+                .expect("failed to instantiate component");
+            let result = runtime.call_named(&name, "run", &[]).await;
+
+            if call < 3 {
+                assert_eq!(result.unwrap(), vec![Val::U32(7)]);
+            } else {
+                let err = result.expect_err("call past the limit should trap");
+                match err {
+                    RuntimeError::RateLimited { function } => {
+                        assert_eq!(function, "its:test/crypto#hash")
+                    }
+                    other => panic!("expected a rate limit error, got {other:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_rejects_limits_on_functions_it_cannot_wrap() {
+        let result = RuntimeBuilder::new()
+            .nested_view(EmptyView)
+            .rate_limit_host_fn(
+                "its:test/crypto",
+                "hash",
+                RateLimit::new(3, Duration::from_secs(3600)),
+            )
+            .build();
+
+        match result {
+            Err(RuntimeError::LinkerSetup(err)) => {
+                assert!(err.to_string().contains("its:test/crypto#hash"))
+            }
+            Err(err) => panic!("expected a linker setup error, got {err}"),
+            Ok(_) => panic!("expected building to fail"),
+        }
+    }
+}
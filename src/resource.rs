@@ -0,0 +1,83 @@
+use wasmtime::component::{Resource, ResourceTable, ResourceTableError};
+
+/// A resource handle that deletes its table entry when dropped.
+///
+/// Call [`OwnedResource::into_inner`] to hand the handle back without deleting it, e.g. when
+/// returning it to the guest.
+pub struct OwnedResource<'a, R: 'static> {
+    table: &'a mut ResourceTable,
+    resource: Option<Resource<R>>,
+}
+
+impl<'a, R> OwnedResource<'a, R>
+where
+    R: 'static,
+{
+    pub(crate) fn new(table: &'a mut ResourceTable, resource: Resource<R>) -> Self {
+        Self {
+            table,
+            resource: Some(resource),
+        }
+    }
+
+    pub fn resource(&self) -> &Resource<R> {
+        self.resource
+            .as_ref()
+            .expect("resource is only taken on drop or release")
+    }
+
+    pub fn get(&self) -> Result<&R, ResourceTableError> {
+        self.table.get(self.resource())
+    }
+
+    pub fn get_mut(&mut self) -> Result<&mut R, ResourceTableError> {
+        let resource = self
+            .resource
+            .as_ref()
+            .expect("resource is only taken on drop or release");
+        self.table.get_mut(resource)
+    }
+
+    /// Deletes the entry now and returns its value.
+    pub fn delete(mut self) -> Result<R, ResourceTableError> {
+        let resource = self.resource.take().expect("resource is only taken once");
+        self.table.delete(resource)
+    }
+
+    /// Releases ownership without deleting the table entry.
+    pub fn into_inner(mut self) -> Resource<R> {
+        self.resource.take().expect("resource is only taken once")
+    }
+}
+
+impl<R> Drop for OwnedResource<'_, R>
+where
+    R: 'static,
+{
+    fn drop(&mut self) {
+        if let Some(resource) = self.resource.take() {
+            let _ = self.table.delete(resource);
+        }
+    }
+}
+
+/// A value the host grants to a guest as an unforgeable handle.
+///
+/// Capabilities are only minted by [`crate::RuntimeView::grant_capability`]. Host functions
+/// receiving a `Resource<Capability<C>>` look it up with [`crate::RuntimeView::capability`],
+/// which fails for handles that were never granted, were revoked, or point at another type.
+pub struct Capability<C>(C);
+
+impl<C> Capability<C> {
+    pub(crate) fn new(value: C) -> Self {
+        Self(value)
+    }
+
+    pub fn get(&self) -> &C {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> C {
+        self.0
+    }
+}
@@ -0,0 +1,226 @@
+use std::sync::Arc;
+
+use wasmtime::component::{Linker, Val};
+
+use crate::{global::DynHostFn, rate_limit::RateLimits, NestedView, Runtime};
+
+/// A default implementation for an import the host may not provide.
+pub struct ImportShim {
+    interface: Option<String>,
+    name: String,
+    func: DynHostFn,
+}
+
+impl ImportShim {
+    /// Shims a function imported at the component root.
+    pub fn root<F>(name: impl Into<String>, func: F) -> Self
+    where
+        F: Fn(&[Val], &mut [Val]) -> anyhow::Result<()> + Send + Sync + 'static,
+    {
+        Self {
+            interface: None,
+            name: name.into(),
+            func: Arc::new(func),
+        }
+    }
+
+    /// Shims a function of an imported interface, e.g. `my:pkg/iface`.
+    pub fn interface<F>(interface: impl Into<String>, name: impl Into<String>, func: F) -> Self
+    where
+        F: Fn(&[Val], &mut [Val]) -> anyhow::Result<()> + Send + Sync + 'static,
+    {
+        Self {
+            interface: Some(interface.into()),
+            name: name.into(),
+            func: Arc::new(func),
+        }
+    }
+
+    /// Shims a function of an imported interface with one that always returns `results`.
+    pub fn returning(
+        interface: impl Into<String>,
+        name: impl Into<String>,
+        results: Vec<Val>,
+    ) -> Self {
+        Self::interface(interface, name, move |_, out| {
+            anyhow::ensure!(
+                out.len() == results.len(),
+                "shim returns {} values but the import expects {}",
+                results.len(),
+                out.len()
+            );
+            out.clone_from_slice(&results);
+            Ok(())
+        })
+    }
+
+    fn path(&self) -> String {
+        match &self.interface {
+            Some(interface) => format!("{interface}#{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Paths (`interface#name`, or just `name` at the root) of the import shims that were linked
+    /// because the host didn't provide them.
+    pub fn shimmed_imports(&self) -> &[String] {
+        &self.shimmed_imports
+    }
+}
+
+/// Defines every shim whose import the linker doesn't already satisfy and returns their paths.
+///
+/// Shims only fill in whole missing interfaces or root functions: an interface the host
+/// defines itself is left untouched, even if it lacks a shimmed function.
+pub(crate) fn add_shims_to_linker<T>(
+    linker: &mut Linker<T>,
+    shims: Vec<ImportShim>,
+    rate_limits: &RateLimits,
+) -> anyhow::Result<Vec<String>> {
+    let mut active = Vec::new();
+
+    let mut interfaces: Vec<Option<String>> = shims.iter().map(|s| s.interface.clone()).collect();
+    interfaces.sort_unstable();
+    interfaces.dedup();
+
+    for interface in interfaces {
+        let shims = shims.iter().filter(|s| s.interface == interface);
+
+        let mut instance = match &interface {
+            Some(interface) => match linker.instance(interface) {
+                Ok(instance) => instance,
+                // The host already provides this interface.
+                Err(_) => continue,
+            },
+            None => linker.root(),
+        };
+
+        for shim in shims {
+            let path = shim.path();
+            let func = rate_limits.apply(path.clone(), shim.func.clone());
+            let defined =
+                instance.func_new(&shim.name, move |_, params, results| func(params, results));
+
+            match defined {
+                Ok(()) => {}
+                // A root function that fails to define is already provided by the host, unless
+                // another shim defined it.
+                Err(_) if interface.is_none() && !active.contains(&path) => continue,
+                Err(err) => {
+                    return Err(err.context(format!("failed to link import shim `{path}`")));
+                }
+            }
+
+            #[cfg(feature = "tracing")]
+            tracing::info!(shim = %path, "linked import shim");
+            active.push(path);
+        }
+    }
+
+    Ok(active)
+}
+
+#[cfg(test)]
+mod shim_test {
+    use wasmtime::component::{Linker, Val};
+
+    use crate::{ImportShim, NestedView, RuntimeBuilder, RuntimeError, RuntimeView};
+
+    const IMPORTS_FLAG: &str = r#"
+        (component
+            (import "its:test/optional" (instance $o
+                (export "flag" (func (result u32)))))
+            (core func $flag (canon lower (func $o "flag")))
+            (core module $m
+                (import "host" "flag" (func $flag (result i32)))
+                (func (export "run") (result i32) (call $flag)))
+            (core instance $host (export "flag" (func $flag)))
+            (core instance $i (instantiate $m (with "host" (instance $host))))
+            (func (export "run") (result u32) (canon lift (core func $i "run")))
+        )
+    "#;
+
+    struct FlagView(Option<u32>);
+
+    impl NestedView for FlagView {
+        fn add_all_to_linker(
+            &mut self,
+            linker: &mut Linker<RuntimeView<Self>>,
+        ) -> anyhow::Result<()> {
+            if let Some(flag) = self.0 {
+                linker
+                    .instance("its:test/optional")?
+                    .func_wrap("flag", move |_, ()| Ok((flag,)))?;
+            }
+            Ok(())
+        }
+    }
+
+    async fn run(view: FlagView) -> (u32, Vec<String>) {
+        let mut runtime = RuntimeBuilder::new()
+            .nested_view(view)
+            .shim_missing_imports([ImportShim::returning(
+                "its:test/optional",
+                "flag",
+                vec![Val::U32(0)],
+            )])
+            .build()
+            .expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(IMPORTS_FLAG)
+            .expect("failed to compile component");
+        let instance = runtime
+            .instantiate_component(&component)
+            .await
+            .expect("failed to instantiate component");
+        let run = instance
+            .get_typed_func::<(), (u32,)>(&mut runtime.store, "run")
+            .expect("missing run export");
+
+        let (result,) = run
+            .call_async(&mut runtime.store, ())
+            .await
+            .expect("failed to invoke run");
+        run.post_return_async(&mut runtime.store)
+            .await
+            .expect("failed to post return");
+
+        (result, runtime.shimmed_imports().to_vec())
+    }
+
+    #[tokio::test]
+    async fn it_shims_missing_imports() {
+        let (result, shimmed) = run(FlagView(None)).await;
+
+        assert_eq!(result, 0);
+        assert_eq!(shimmed, vec!["its:test/optional#flag".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn it_prefers_host_implementations() {
+        let (result, shimmed) = run(FlagView(Some(5))).await;
+
+        assert_eq!(result, 5);
+        assert!(shimmed.is_empty());
+    }
+
+    #[test]
+    fn it_reports_shims_that_fail_to_link() {
+        let err = RuntimeBuilder::new()
+            .nested_view(FlagView(None))
+            .shim_missing_imports([
+                ImportShim::returning("its:test/optional", "flag", vec![Val::U32(0)]),
+                ImportShim::returning("its:test/optional", "flag", vec![Val::U32(1)]),
+            ])
+            .build()
+            .err()
+            .expect("a duplicate shim should fail to link");
+        assert!(matches!(err, RuntimeError::LinkerSetup(_)));
+    }
+}
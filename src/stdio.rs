@@ -0,0 +1,126 @@
+use wasmtime_wasi::pipe::MemoryOutputPipe;
+
+use crate::{NestedView, Runtime};
+
+/// Bytes captured per stream by [`crate::RuntimeBuilder::capture_stdout`] and
+/// [`crate::RuntimeBuilder::capture_stderr`] over a runtime's lifetime. Writes past it fail in
+/// the guest.
+pub const STDIO_CAPTURE_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Bytes buffered between the host and guest ends of a [`crate::RuntimeBuilder::stdin_pipe`].
+/// Host writes wait once it is full.
+pub const STDIN_PIPE_BUFFER: usize = 64 * 1024;
+
+/// An in-memory guest output stream, read incrementally.
+pub(crate) struct CapturedPipe {
+    pipe: MemoryOutputPipe,
+    read: usize,
+}
+
+impl CapturedPipe {
+    pub(crate) fn new() -> Self {
+        Self {
+            pipe: MemoryOutputPipe::new(STDIO_CAPTURE_LIMIT),
+            read: 0,
+        }
+    }
+
+    pub(crate) fn pipe(&self) -> MemoryOutputPipe {
+        self.pipe.clone()
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<u8> {
+        let contents = self.pipe.contents();
+        let unread = contents[self.read..].to_vec();
+        self.read = contents.len();
+        unread
+    }
+}
+
+impl<T> Runtime<T>
+where
+    T: NestedView,
+{
+    /// Returns what the guest wrote to stdout since the last call, or nothing if stdout isn't
+    /// captured.
+    ///
+    /// Writes go straight to the buffer, so everything a finished call printed is available.
+    pub fn take_stdout(&mut self) -> Vec<u8> {
+        self.captured_stdout
+            .as_mut()
+            .map(CapturedPipe::take)
+            .unwrap_or_default()
+    }
+
+    /// Returns what the guest wrote to stderr since the last call, or nothing if stderr isn't
+    /// captured.
+    pub fn take_stderr(&mut self) -> Vec<u8> {
+        self.captured_stderr
+            .as_mut()
+            .map(CapturedPipe::take)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod capture_stdio_test {
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_captures_guest_output() {
+        let mut runtime = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .capture_stdout()
+            .capture_stderr()
+            .build()
+            .expect("Failed to build runtime");
+
+        // The guest exits with a failure status, which surfaces as an error.
+        let _ = run_guest(&mut runtime, "command_output").await;
+
+        assert_eq!(runtime.take_stdout(), b"to stdout\n");
+        assert_eq!(runtime.take_stderr(), b"to stderr\n");
+        assert!(runtime.take_stdout().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod stdin_pipe_test {
+    use std::time::Duration;
+
+    use tokio::io::AsyncWriteExt;
+
+    use crate::{
+        test_support::{run_guest, EmptyView},
+        RuntimeBuilder,
+    };
+
+    #[tokio::test]
+    async fn it_feeds_stdin_while_the_guest_runs() {
+        let (builder, mut stdin) = RuntimeBuilder::new()
+            .with_wasi(true)
+            .nested_view(EmptyView)
+            .stdin_pipe();
+        let mut runtime = builder
+            .capture_stdout()
+            .build()
+            .expect("Failed to build runtime");
+
+        let writer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            stdin.write_all(b"hello\n").await.unwrap();
+            stdin
+        });
+
+        run_guest(&mut runtime, "stdin_echo")
+            .await
+            .expect("command failed");
+        writer.await.unwrap();
+
+        assert_eq!(runtime.take_stdout(), b"echo: hello\n");
+    }
+}
@@ -0,0 +1,91 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use wasmtime::{component::ResourceTable, Engine, Store, StoreLimitsBuilder, UpdateDeadline};
+use wasmtime_wasi::{DirPerms, FilePerms, WasiCtxBuilder};
+
+use crate::{NestedView, RuntimeView};
+
+/// A change to a store's WASI context, recorded by the builder so it can be applied to every
+/// store the runtime creates.
+pub(crate) type WasiSetting = Arc<dyn Fn(&mut WasiCtxBuilder) + Send + Sync>;
+
+/// How the builder configured the runtime's store, kept so [`crate::Runtime::spawn_instance`]
+/// can set up new stores the same way.
+pub(crate) struct StoreSettings {
+    /// Whether the engine was given to the builder, so its epoch and fuel settings are unknown.
+    pub(crate) shared_engine: bool,
+    pub(crate) wasi: Vec<WasiSetting>,
+    /// Whether the runtime's stdin is a [`crate::RuntimeBuilder::stdin_pipe`], which can only
+    /// feed one store.
+    pub(crate) stdin_piped: bool,
+    pub(crate) capture_stdout: bool,
+    pub(crate) capture_stderr: bool,
+    /// The host's environment as it was at build time, minus variables set on the builder.
+    pub(crate) inherited_env: Vec<(String, String)>,
+    pub(crate) preopens: Vec<(PathBuf, String, DirPerms, FilePerms)>,
+    pub(crate) resource_table_capacity: Option<usize>,
+    pub(crate) max_memory_bytes: Option<usize>,
+    pub(crate) max_table_elements: Option<usize>,
+}
+
+impl StoreSettings {
+    /// A WASI context builder with inherited stdio and every recorded setting applied, for the
+    /// caller to add per-store streams to.
+    pub(crate) fn wasi_ctx(&self) -> anyhow::Result<WasiCtxBuilder> {
+        let mut wasi_ctx = WasiCtxBuilder::new();
+        wasi_ctx.inherit_stdio();
+        for setting in &self.wasi {
+            setting(&mut wasi_ctx);
+        }
+        for (key, value) in &self.inherited_env {
+            wasi_ctx.env(key, value);
+        }
+        for (host_path, guest_path, dir_perms, file_perms) in &self.preopens {
+            wasi_ctx
+                .preopened_dir(host_path, guest_path, *dir_perms, *file_perms)
+                .with_context(|| format!("failed to preopen {}", host_path.display()))?;
+        }
+        Ok(wasi_ctx)
+    }
+
+    /// A store for `view` with the recorded resource table capacity and memory and table limits.
+    pub(crate) fn store<T>(
+        &self,
+        engine: &Engine,
+        mut view: RuntimeView<T>,
+    ) -> anyhow::Result<Store<RuntimeView<T>>>
+    where
+        T: NestedView,
+    {
+        if let Some(capacity) = self.resource_table_capacity {
+            view.table = ResourceTable::with_capacity(capacity);
+        }
+        let limited = self.max_memory_bytes.is_some() || self.max_table_elements.is_some();
+        if limited {
+            let mut limits = StoreLimitsBuilder::new().trap_on_grow_failure(true);
+            if let Some(bytes) = self.max_memory_bytes {
+                limits = limits.memory_size(bytes);
+            }
+            if let Some(elements) = self.max_table_elements {
+                limits = limits.table_elements(elements.try_into().unwrap_or(u32::MAX as _));
+            }
+            view.limits = limits.build();
+        }
+
+        let mut store = Store::new(engine, view);
+        if limited {
+            store.limiter(|view| &mut view.limits);
+        }
+        if self.shared_engine {
+            // Stores start with a deadline and fuel of zero, which would fail the first call on
+            // an engine that interrupts on epochs or consumes fuel.
+            store.set_epoch_deadline(1);
+            store.epoch_deadline_callback(|_| Ok(UpdateDeadline::Continue(1)));
+            if store.get_fuel().is_ok() {
+                store.set_fuel(u64::MAX)?;
+            }
+        }
+        Ok(store)
+    }
+}
@@ -0,0 +1,56 @@
+/// Evaluates `$body` inside an `info` span called `$name` carrying the given fields, and records
+/// how long it took. Without the `tracing` feature this is just `$body`.
+macro_rules! traced {
+    ($name:literal $(, $field:ident = $value:expr)*; $body:expr) => {{
+        #[cfg(feature = "tracing")]
+        let (span, started) = (
+            tracing::info_span!(
+                $name
+                $(, $field = $value)*,
+                elapsed_us = tracing::field::Empty
+            ),
+            std::time::Instant::now(),
+        );
+        #[cfg(feature = "tracing")]
+        let entered = span.enter();
+        let output = $body;
+        #[cfg(feature = "tracing")]
+        {
+            drop(entered);
+            $crate::trace::finish(&span, started);
+        }
+        output
+    }};
+}
+
+/// Like [`traced!`], but awaits `$future` instrumented with the span instead of entering it, so
+/// the span follows the future across threads.
+macro_rules! traced_async {
+    ($name:literal $(, $field:ident = $value:expr)*; $future:expr) => {{
+        #[cfg(feature = "tracing")]
+        let output = {
+            use tracing::Instrument;
+            let span = tracing::info_span!(
+                $name
+                $(, $field = $value)*,
+                elapsed_us = tracing::field::Empty
+            );
+            let started = std::time::Instant::now();
+            let output = $future.instrument(span.clone()).await;
+            $crate::trace::finish(&span, started);
+            output
+        };
+        #[cfg(not(feature = "tracing"))]
+        let output = $future.await;
+        output
+    }};
+}
+
+pub(crate) use {traced, traced_async};
+
+#[cfg(feature = "tracing")]
+pub(crate) fn finish(span: &tracing::Span, started: std::time::Instant) {
+    let elapsed = started.elapsed();
+    span.record("elapsed_us", elapsed.as_micros() as u64);
+    tracing::debug!(parent: span, ?elapsed, "finished");
+}
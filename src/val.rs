@@ -0,0 +1,365 @@
+use anyhow::{anyhow, bail};
+use wasmtime::component::Val;
+
+/// Converts a Rust value into a dynamically typed component value.
+///
+/// Use [`impl_record!`](crate::impl_record) and [`impl_variant!`](crate::impl_variant) to
+/// implement this for structs and enums.
+pub trait ToVal {
+    fn to_val(&self) -> Val;
+}
+
+/// Converts a dynamically typed component value back into a Rust value.
+pub trait FromVal: Sized {
+    fn from_val(val: &Val) -> anyhow::Result<Self>;
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl ToVal for $ty {
+                fn to_val(&self) -> Val {
+                    Val::$variant(self.clone())
+                }
+            }
+
+            impl FromVal for $ty {
+                fn from_val(val: &Val) -> anyhow::Result<Self> {
+                    match val {
+                        Val::$variant(value) => Ok(value.clone()),
+                        other => bail!(
+                            "expected {}, found {other:?}",
+                            stringify!($variant)
+                        ),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive! {
+    bool => Bool,
+    i8 => S8,
+    u8 => U8,
+    i16 => S16,
+    u16 => U16,
+    i32 => S32,
+    u32 => U32,
+    i64 => S64,
+    u64 => U64,
+    f32 => Float32,
+    f64 => Float64,
+    char => Char,
+    String => String,
+}
+
+impl<T> ToVal for Vec<T>
+where
+    T: ToVal,
+{
+    fn to_val(&self) -> Val {
+        Val::List(self.iter().map(ToVal::to_val).collect())
+    }
+}
+
+impl<T> FromVal for Vec<T>
+where
+    T: FromVal,
+{
+    fn from_val(val: &Val) -> anyhow::Result<Self> {
+        match val {
+            Val::List(values) => values.iter().map(T::from_val).collect(),
+            other => bail!("expected List, found {other:?}"),
+        }
+    }
+}
+
+impl<T> ToVal for Option<T>
+where
+    T: ToVal,
+{
+    fn to_val(&self) -> Val {
+        Val::Option(self.as_ref().map(|value| Box::new(value.to_val())))
+    }
+}
+
+impl<T> FromVal for Option<T>
+where
+    T: FromVal,
+{
+    fn from_val(val: &Val) -> anyhow::Result<Self> {
+        match val {
+            Val::Option(value) => value.as_deref().map(T::from_val).transpose(),
+            other => bail!("expected Option, found {other:?}"),
+        }
+    }
+}
+
+/// Converts a Rust identifier (`snake_case` or `CamelCase`) into a WIT `kebab-case` name.
+#[doc(hidden)]
+pub fn kebab_case(name: &str) -> String {
+    let mut kebab = String::with_capacity(name.len());
+    for (i, c) in name.chars().enumerate() {
+        if c == '_' {
+            kebab.push('-');
+        } else if c.is_uppercase() {
+            if i > 0 && !kebab.ends_with('-') {
+                kebab.push('-');
+            }
+            kebab.extend(c.to_lowercase());
+        } else {
+            kebab.push(c);
+        }
+    }
+    kebab
+}
+
+#[doc(hidden)]
+pub fn record_field<T>(val: &Val, name: &str) -> anyhow::Result<T>
+where
+    T: FromVal,
+{
+    let Val::Record(fields) = val else {
+        bail!("expected Record, found {val:?}");
+    };
+    let name = kebab_case(name);
+    let (_, value) = fields
+        .iter()
+        .find(|(field, _)| *field == name)
+        .ok_or_else(|| anyhow!("record is missing field `{name}`"))?;
+    T::from_val(value)
+}
+
+#[doc(hidden)]
+pub fn variant_payload<T>(payload: Option<&Val>, case: &str) -> anyhow::Result<T>
+where
+    T: FromVal,
+{
+    T::from_val(payload.ok_or_else(|| anyhow!("variant case `{case}` is missing its payload"))?)
+}
+
+/// Implements [`ToVal`] and [`FromVal`] for a struct with named fields, mapping it to a record.
+///
+/// Field names are converted to kebab-case, so `max_len` maps to the record field `max-len`.
+///
+/// ```ignore
+/// struct Point { x: u32, y: u32 }
+/// its_wasmtime::impl_record!(Point { x, y });
+/// ```
+#[macro_export]
+macro_rules! impl_record {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::ToVal for $ty {
+            fn to_val(&self) -> $crate::wasmtime::component::Val {
+                $crate::wasmtime::component::Val::Record(vec![
+                    $((
+                        $crate::val::kebab_case(stringify!($field)),
+                        $crate::ToVal::to_val(&self.$field),
+                    )),*
+                ])
+            }
+        }
+
+        impl $crate::FromVal for $ty {
+            fn from_val(val: &$crate::wasmtime::component::Val) -> $crate::anyhow::Result<Self> {
+                Ok(Self {
+                    $($field: $crate::val::record_field(val, stringify!($field))?),*
+                })
+            }
+        }
+    };
+}
+
+/// Implements [`ToVal`] and [`FromVal`] for an enum, mapping it to a variant.
+///
+/// Cases may carry at most one payload. Case names are converted to kebab-case, so `BigSquare`
+/// maps to the variant case `big-square`.
+///
+/// ```ignore
+/// enum Shape { Circle(f64), Square(f64), Empty }
+/// its_wasmtime::impl_variant!(Shape { Circle(f64), Square(f64), Empty });
+/// ```
+#[macro_export]
+macro_rules! impl_variant {
+    ($ty:ident { $($case:ident $(($payload:ty))?),* $(,)? }) => {
+        impl $crate::ToVal for $ty {
+            fn to_val(&self) -> $crate::wasmtime::component::Val {
+                match self {
+                    $(
+                        $crate::__variant_pattern!(value; $ty::$case $(: $payload)?) => {
+                            $crate::wasmtime::component::Val::Variant(
+                                $crate::val::kebab_case(stringify!($case)),
+                                $crate::__variant_payload_to_val!(value $(: $payload)?),
+                            )
+                        }
+                    )*
+                }
+            }
+        }
+
+        impl $crate::FromVal for $ty {
+            fn from_val(val: &$crate::wasmtime::component::Val) -> $crate::anyhow::Result<Self> {
+                let $crate::wasmtime::component::Val::Variant(case, payload) = val else {
+                    $crate::anyhow::bail!("expected Variant, found {val:?}");
+                };
+                $(
+                    if *case == $crate::val::kebab_case(stringify!($case)) {
+                        return Ok($crate::__variant_from_val!(
+                            payload, case; $ty::$case $(: $payload)?
+                        ));
+                    }
+                )*
+                $crate::anyhow::bail!("unknown variant case `{case}`")
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __variant_pattern {
+    ($value:ident; $ty:ident::$case:ident) => {
+        $ty::$case
+    };
+    ($value:ident; $ty:ident::$case:ident: $payload:ty) => {
+        $ty::$case($value)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __variant_payload_to_val {
+    ($value:ident) => {
+        None
+    };
+    ($value:ident: $payload:ty) => {
+        Some(Box::new($crate::ToVal::to_val($value)))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __variant_from_val {
+    ($payload:ident, $name:ident; $ty:ident::$case:ident) => {
+        $ty::$case
+    };
+    ($payload:ident, $name:ident; $ty:ident::$case:ident: $ty_payload:ty) => {
+        $ty::$case($crate::val::variant_payload::<$ty_payload>(
+            $payload.as_deref(),
+            $name,
+        )?)
+    };
+}
+
+#[cfg(test)]
+mod val_test {
+    use wasmtime::component::Val;
+
+    use crate::{runtime, test_support::EmptyView, FromVal, ToVal};
+
+    #[derive(Debug, PartialEq)]
+    struct Point {
+        x: u32,
+        y: u32,
+    }
+
+    crate::impl_record!(Point { x, y });
+
+    #[derive(Debug, PartialEq)]
+    struct Labelled {
+        point_label: String,
+        points: Vec<Point>,
+        note: Option<String>,
+    }
+
+    crate::impl_record!(Labelled {
+        point_label,
+        points,
+        note,
+    });
+
+    #[derive(Debug, PartialEq)]
+    enum Shape {
+        Circle(f64),
+        BigSquare(Point),
+        Empty,
+    }
+
+    crate::impl_variant!(Shape {
+        Circle(f64),
+        BigSquare(Point),
+        Empty,
+    });
+
+    const SWAP: &str = r#"
+        (component
+            (core module $m
+                (memory (export "memory") 1)
+                (func (export "swap") (param i32 i32) (result i32)
+                    (i32.store (i32.const 0) (local.get 1))
+                    (i32.store (i32.const 4) (local.get 0))
+                    (i32.const 0)))
+            (core instance $i (instantiate $m))
+            (alias core export $i "memory" (core memory $mem))
+            (type $point (record (field "x" u32) (field "y" u32)))
+            (export $point-export "point" (type $point))
+            (func (export "swap") (param "p" $point-export) (result $point-export)
+                (canon lift (core func $i "swap") (memory $mem)))
+        )
+    "#;
+
+    #[test]
+    fn it_round_trips_records() {
+        let labelled = Labelled {
+            point_label: "corners".into(),
+            points: vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }],
+            note: None,
+        };
+
+        let val = labelled.to_val();
+        let Val::Record(fields) = &val else {
+            panic!("expected a record, got {val:?}");
+        };
+        assert_eq!(fields[0].0, "point-label");
+
+        assert_eq!(Labelled::from_val(&val).unwrap(), labelled);
+    }
+
+    #[test]
+    fn it_round_trips_variants() {
+        for shape in [
+            Shape::Circle(1.5),
+            Shape::BigSquare(Point { x: 2, y: 2 }),
+            Shape::Empty,
+        ] {
+            assert_eq!(Shape::from_val(&shape.to_val()).unwrap(), shape);
+        }
+
+        assert_eq!(Shape::Empty.to_val(), Val::Variant("empty".into(), None));
+        assert!(matches!(
+            Shape::BigSquare(Point { x: 0, y: 0 }).to_val(),
+            Val::Variant(case, Some(_)) if case == "big-square"
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_round_trips_records_through_components() {
+        let mut runtime = runtime(false, EmptyView).expect("Failed to build runtime");
+
+        let component = runtime
+            .load_component(SWAP)
+            .expect("failed to compile component");
+        runtime
+            .instantiate_named("swap", &component)
+            .await
+            .expect("failed to instantiate component");
+
+        let results = runtime
+            .call_named("swap", "swap", &[Point { x: 40, y: 2 }.to_val()])
+            .await
+            .expect("failed to invoke swap");
+
+        assert_eq!(Point::from_val(&results[0]).unwrap(), Point { x: 2, y: 40 });
+    }
+}
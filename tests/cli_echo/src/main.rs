@@ -0,0 +1,11 @@
+fn main() {
+    for arg in std::env::args() {
+        println!("{arg}");
+    }
+
+    let mut vars: Vec<_> = std::env::vars().collect();
+    vars.sort();
+    for (key, value) in vars {
+        println!("{key}={value}");
+    }
+}
@@ -0,0 +1,31 @@
+use wasi::http::{
+    outgoing_handler,
+    types::{Fields, OutgoingRequest, Scheme},
+};
+
+/// Fetches `/` from the authority given as the first argument and prints the response body.
+fn main() {
+    let authority = std::env::args().nth(1).expect("missing authority argument");
+
+    let request = OutgoingRequest::new(Fields::new());
+    request.set_scheme(Some(&Scheme::Http)).unwrap();
+    request.set_authority(Some(&authority)).unwrap();
+    request.set_path_with_query(Some("/")).unwrap();
+
+    let response = outgoing_handler::handle(request, None).expect("failed to send request");
+    response.subscribe().block();
+    let response = response
+        .get()
+        .expect("response is ready")
+        .expect("response was taken")
+        .expect("request failed");
+
+    let body = response.consume().expect("body was taken");
+    let stream = body.stream().expect("stream was taken");
+    let mut bytes = Vec::new();
+    while let Ok(chunk) = stream.blocking_read(4096) {
+        bytes.extend(chunk);
+    }
+
+    print!("{}", String::from_utf8_lossy(&bytes));
+}
@@ -0,0 +1,6 @@
+use wasi::random::{insecure, random};
+
+fn main() {
+    println!("{:?}", random::get_random_bytes(16));
+    println!("{:?}", insecure::get_insecure_random_bytes(16));
+}
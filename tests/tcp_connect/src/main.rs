@@ -0,0 +1,34 @@
+use wasi::sockets::{
+    instance_network::instance_network,
+    network::{IpAddressFamily, IpSocketAddress, Ipv4SocketAddress},
+    tcp_create_socket::create_tcp_socket,
+};
+
+/// Connects to 127.0.0.1 on the port given as the first argument.
+fn main() {
+    let port = std::env::args()
+        .nth(1)
+        .and_then(|port| port.parse().ok())
+        .expect("expected a port argument");
+
+    let network = instance_network();
+    let socket = create_tcp_socket(IpAddressFamily::Ipv4).expect("failed to create socket");
+    let address = IpSocketAddress::Ipv4(Ipv4SocketAddress {
+        port,
+        address: (127, 0, 0, 1),
+    });
+
+    if let Err(error) = socket.start_connect(&network, address) {
+        eprintln!("connect failed: {error:?}");
+        std::process::exit(1);
+    }
+    socket.subscribe().block();
+
+    match socket.finish_connect() {
+        Ok(_) => println!("connected"),
+        Err(error) => {
+            eprintln!("connect failed: {error:?}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    std::fs::write("/data/hello.txt", "hello from the guest").expect("failed to write file");
+}